@@ -0,0 +1,597 @@
+/// Alarm primitives, pure logic driven by the RTC epoch
+use crate::datetime::{DateTime, DayMask};
+use crate::locale::{Locale, ENGLISH};
+use crate::ramp::{Levels, Ramp};
+use crate::timezone::TimeZone;
+use heapless::Vec;
+
+pub const MAX_ALARMS: usize = 4;
+// if the RTC tick arrives later than this (e.g the clock was moved
+// forward) the alarm is not fired, only re-armed
+const GRACE_SECONDS: u32 = 60;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Alarm {
+    pub hour: u8,
+    pub min: u8,
+    pub enabled: bool,
+    /// days on which the alarm repeats, an empty mask means that it rings only once
+    pub repeat: DayMask,
+    /// the light and the sound that go up before the alarm rings
+    pub ramp: Ramp,
+}
+
+impl Alarm {
+    /// one shot alarm that rings at the next `hour:min`
+    pub fn new(hour: u8, min: u8) -> Self {
+        Self::repeating(hour, min, DayMask::NONE)
+    }
+
+    pub fn repeating(hour: u8, min: u8, repeat: DayMask) -> Self {
+        Self {
+            hour,
+            min,
+            enabled: true,
+            repeat,
+            ramp: Ramp::OFF,
+        }
+    }
+
+    pub fn with_ramp(self, ramp: Ramp) -> Self {
+        Self { ramp, ..self }
+    }
+
+    pub fn is_one_shot(&self) -> bool {
+        self.repeat.is_empty()
+    }
+
+    /// the time and the days or `off` in the language of `locale`
+    pub fn write<W: ::core::fmt::Write>(&self, w: &mut W, locale: &Locale) -> ::core::fmt::Result {
+        write!(w, "{:02}:{:02} ", self.hour, self.min)?;
+        match (self.enabled, self.is_one_shot()) {
+            (false, _) => w.write_str(locale.alarm_off),
+            (true, true) => w.write_str(locale.alarm_once),
+            (true, false) => self.repeat.write(w, locale),
+        }
+    }
+
+    /// the first UTC epoch strictly after `now` at which this alarm should ring (ignoring the
+    /// `enabled` flag), `None` if the alarm time is invalid or out of the RTC range. The alarm
+    /// time is the local time in `zone`
+    pub fn next_trigger(&self, now: u32, zone: &TimeZone) -> Option<u32> {
+        let mut local = zone.datetime(now);
+        // in the hour repeated when the summer time ends the next local
+        // occurrence could be the first pass of that hour, already past in UTC
+        for _ in 0..2 {
+            local = local
+                .next_occurrence(self.hour, self.min, self.repeat)
+                .ok()?;
+            let epoch = zone.to_utc(local.to_epoch().ok()?)?;
+            if epoch > now {
+                return Some(epoch);
+            }
+        }
+        None
+    }
+}
+
+impl ::core::fmt::Display for Alarm {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
+        self.write(f, &ENGLISH)
+    }
+}
+
+/// What the snooze buttons do while an alarm is ringing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Snooze {
+    /// minutes until the alarm rings again
+    pub minutes: u8,
+    /// times that an alarm can be snoozed, after that it can only be dismissed
+    pub max_count: u8,
+    /// seconds ringing before it stops by itself, zero is never
+    pub ring_timeout: u16,
+}
+
+impl Default for Snooze {
+    fn default() -> Self {
+        Self {
+            minutes: 9,
+            max_count: 3,
+            ring_timeout: 300,
+        }
+    }
+}
+
+/// the epoch `minutes` after `now`
+pub fn snooze_until(now: u32, minutes: u8) -> Option<u32> {
+    let until = DateTime::new(now).add_minutes(i64::from(minutes)).ok()?;
+    until.to_epoch().ok()
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Slot {
+    alarm: Alarm,
+    next: Option<u32>,
+}
+
+/// The set of alarms of the clock with the cached next trigger epoch of each one
+pub struct Alarms {
+    slots: Vec<Slot, MAX_ALARMS>,
+    /// the index of the snoozed alarm and the epoch when it rings again
+    snoozed: Option<(usize, u32)>,
+    /// the index of the alarm that rang and was not snoozed or dismissed yet
+    ringing: Option<usize>,
+    /// where the alarm times are
+    zone: TimeZone,
+}
+
+impl Alarms {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            snoozed: None,
+            ringing: None,
+            zone: TimeZone::UTC,
+        }
+    }
+
+    /// the alarm times are the local time of `zone`, they are armed again from `now`
+    pub fn set_zone(&mut self, zone: TimeZone, now: u32) {
+        self.zone = zone;
+        self.rearm(now);
+    }
+
+    /// add a new alarm armed from `now`, returns the alarm back if there is no more space
+    pub fn add(&mut self, alarm: Alarm, now: u32) -> Result<usize, Alarm> {
+        let slot = Slot {
+            alarm,
+            next: Self::arm(&alarm, now, &self.zone),
+        };
+        self.slots.push(slot).map_err(|slot| slot.alarm)?;
+        Ok(self.slots.len() - 1)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Alarm> {
+        if index < self.slots.len() {
+            self.snoozed = None;
+            Some(self.slots.remove(index).alarm)
+        } else {
+            None
+        }
+    }
+
+    /// replace the alarm at `index` and arm it again from `now`
+    pub fn set(&mut self, index: usize, alarm: Alarm, now: u32) -> Option<()> {
+        let slot = self.slots.get_mut(index)?;
+        *slot = Slot {
+            alarm,
+            next: Self::arm(&alarm, now, &self.zone),
+        };
+        Some(())
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Alarm> {
+        self.slots.get(index).map(|slot| &slot.alarm)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Alarm> {
+        self.slots.iter().map(|slot| &slot.alarm)
+    }
+
+    /// recompute all the trigger epochs, call it when the clock has been changed
+    pub fn rearm(&mut self, now: u32) {
+        for slot in self.slots.iter_mut() {
+            slot.next = Self::arm(&slot.alarm, now, &self.zone);
+        }
+    }
+
+    /// ring again the alarm at `index` in the epoch `until`, it replaces a previous snooze
+    pub fn snooze(&mut self, index: usize, until: u32) -> Option<()> {
+        self.slots.get(index)?;
+        self.snoozed = Some((index, until));
+        self.ringing = None;
+        Some(())
+    }
+
+    /// stop the ringing alarm and forget the snoozed one
+    pub fn dismiss(&mut self) {
+        self.snoozed = None;
+        self.ringing = None;
+    }
+
+    pub fn snoozed(&self) -> Option<(usize, u32)> {
+        self.snoozed
+    }
+
+    pub fn ringing(&self) -> Option<usize> {
+        self.ringing
+    }
+
+    /// the index and epoch of the alarm that is going to ring first, the snoozed included
+    pub fn next_trigger(&self) -> Option<(usize, u32)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.next.map(|epoch| (index, epoch)))
+            .chain(self.snoozed)
+            .min_by_key(|&(_, epoch)| epoch)
+    }
+
+    /// the levels of the wake up ramp of the next alarm, a snoozed alarm has no ramp
+    pub fn ramp(&self, now: u32) -> Option<Levels> {
+        let next = self.next_trigger()?;
+        if self.snoozed == Some(next) {
+            return None;
+        }
+        let (index, epoch) = next;
+        self.get(index)?.ramp.levels(epoch.checked_sub(now)?)
+    }
+
+    /// the next epoch when the MCU has to be awake: the start of the ramp or the trigger of the
+    /// next alarm
+    pub fn next_wake_up(&self, now: u32) -> Option<u32> {
+        let next = self.next_trigger()?;
+        let (index, epoch) = next;
+        let ramp = match self.get(index) {
+            Some(alarm) if self.snoozed != Some(next) => alarm.ramp,
+            _ => Ramp::OFF,
+        };
+        let start = ramp.start(epoch);
+        Some(if start > now { start } else { epoch })
+    }
+
+    /// call this on every RTC second tick, returns the index of the alarm that must ring now
+    pub fn check(&mut self, now: u32) -> Option<usize> {
+        if let Some((index, epoch)) = self.snoozed {
+            if epoch <= now {
+                self.snoozed = None;
+                if now - epoch < GRACE_SECONDS {
+                    self.ringing = Some(index);
+                    return Some(index);
+                }
+            }
+        }
+        let (index, slot) = self
+            .slots
+            .iter_mut()
+            .enumerate()
+            .find(|(_, slot)| matches!(slot.next, Some(epoch) if epoch <= now))?;
+        let epoch = slot.next?;
+        if now - epoch >= GRACE_SECONDS {
+            slot.next = Self::arm(&slot.alarm, now, &self.zone);
+            return None;
+        }
+        if slot.alarm.is_one_shot() {
+            slot.alarm.enabled = false;
+        }
+        slot.next = Self::arm(&slot.alarm, now, &self.zone);
+        self.ringing = Some(index);
+        Some(index)
+    }
+
+    fn arm(alarm: &Alarm, now: u32, zone: &TimeZone) -> Option<u32> {
+        if alarm.enabled {
+            alarm.next_trigger(now, zone)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Alarms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::datetime::fixture::{epoch, NOW};
+    use crate::datetime::DayOfWeek;
+    use crate::timezone::DstRule;
+
+    const UTC: TimeZone = TimeZone::UTC;
+    const SECONDS_PER_DAY: u32 = 86400;
+
+    #[test]
+    fn one_shot_later_today_and_tomorrow() {
+        assert_eq!(
+            Alarm::new(23, 0).next_trigger(NOW, &UTC),
+            Some(epoch(2023, 4, 11, 23, 0))
+        );
+        assert_eq!(
+            Alarm::new(7, 30).next_trigger(NOW, &UTC),
+            Some(epoch(2023, 4, 12, 7, 30))
+        );
+        // exactly now is already too late
+        assert_eq!(
+            Alarm::new(22, 18).next_trigger(NOW, &UTC),
+            Some(epoch(2023, 4, 12, 22, 18))
+        );
+    }
+
+    #[test]
+    fn repeating_skips_days_not_in_mask() {
+        let weekend = Alarm::repeating(9, 0, DayMask::WEEKEND);
+        assert_eq!(
+            weekend.next_trigger(NOW, &UTC),
+            Some(epoch(2023, 4, 15, 9, 0))
+        );
+        let tuesday = Alarm::repeating(6, 0, DayMask::NONE.with(DayOfWeek::Tuesday));
+        assert_eq!(
+            tuesday.next_trigger(NOW, &UTC),
+            Some(epoch(2023, 4, 18, 6, 0))
+        );
+        // crossing the end of the month and the year
+        let monday = Alarm::repeating(6, 0, DayMask::NONE.with(DayOfWeek::Monday));
+        assert_eq!(
+            monday.next_trigger(epoch(2023, 12, 26, 0, 0), &UTC),
+            Some(epoch(2024, 1, 1, 6, 0))
+        );
+    }
+
+    #[test]
+    fn invalid_alarm_never_triggers() {
+        assert_eq!(Alarm::new(24, 0).next_trigger(NOW, &UTC), None);
+        assert_eq!(Alarm::new(7, 60).next_trigger(NOW, &UTC), None);
+        assert_eq!(Alarm::new(23, 59).next_trigger(u32::MAX - 10, &UTC), None);
+    }
+
+    #[test]
+    fn check_fires_once_on_simulated_clock() {
+        let mut alarms = Alarms::new();
+        alarms.add(Alarm::new(22, 20), NOW).unwrap();
+        let ring = epoch(2023, 4, 11, 22, 20);
+        let fired: std::vec::Vec<u32> = (NOW..NOW + 2 * SECONDS_PER_DAY)
+            .filter(|&now| alarms.check(now).is_some())
+            .collect();
+        assert_eq!(fired, [ring]);
+        // one shot alarms are disabled after ringing
+        assert!(!alarms.get(0).unwrap().enabled);
+        assert_eq!(alarms.next_trigger(), None);
+    }
+
+    #[test]
+    fn check_repeating_every_week() {
+        let mut alarms = Alarms::new();
+        alarms
+            .add(Alarm::repeating(7, 0, DayMask::WEEKDAYS), NOW)
+            .unwrap();
+        let mut fired = 0;
+        let mut now = NOW;
+        while now < NOW + 7 * SECONDS_PER_DAY {
+            if let Some(index) = alarms.check(now) {
+                assert_eq!(index, 0);
+                fired += 1;
+            }
+            now += 1;
+        }
+        assert_eq!(fired, 5);
+        assert!(alarms.get(0).unwrap().enabled);
+    }
+
+    #[test]
+    fn check_late_tick_only_rearms() {
+        let mut alarms = Alarms::new();
+        alarms.add(Alarm::new(22, 20), NOW).unwrap();
+        let ring = epoch(2023, 4, 11, 22, 20);
+        // a tick a few seconds late still rings
+        assert_eq!(alarms.check(ring + 5), Some(0));
+        alarms.set(0, Alarm::new(22, 20), NOW).unwrap();
+        // but if the clock jumped forward it does not
+        assert_eq!(alarms.check(ring + 3600), None);
+        assert_eq!(alarms.next_trigger(), Some((0, epoch(2023, 4, 12, 22, 20))));
+    }
+
+    #[test]
+    fn next_trigger_is_the_earliest_enabled() {
+        let mut alarms = Alarms::new();
+        assert!(alarms.is_empty());
+        alarms.add(Alarm::new(8, 0), NOW).unwrap();
+        alarms.add(Alarm::new(6, 0), NOW).unwrap();
+        let mut disabled = Alarm::new(5, 0);
+        disabled.enabled = false;
+        alarms.add(disabled, NOW).unwrap();
+        assert_eq!(alarms.next_trigger(), Some((1, epoch(2023, 4, 12, 6, 0))));
+        alarms.add(Alarm::new(1, 0), NOW).unwrap();
+        assert_eq!(alarms.add(Alarm::new(2, 0), NOW), Err(Alarm::new(2, 0)));
+        assert_eq!(alarms.remove(3), Some(Alarm::new(1, 0)));
+        assert_eq!(alarms.len(), 3);
+    }
+
+    #[test]
+    fn snooze_until_crosses_the_calendar() {
+        assert_eq!(snooze_until(NOW, 9), Some(epoch(2023, 4, 11, 22, 27)));
+        // midnight
+        let late = epoch(2023, 4, 11, 23, 55) + 30;
+        assert_eq!(snooze_until(late, 9), Some(epoch(2023, 4, 12, 0, 4) + 30));
+        // end of a month of 30 days and of February in leap and not leap years
+        assert_eq!(
+            snooze_until(epoch(2023, 4, 30, 23, 59), 1),
+            Some(epoch(2023, 5, 1, 0, 0))
+        );
+        assert_eq!(
+            snooze_until(epoch(2023, 2, 28, 23, 50), 15),
+            Some(epoch(2023, 3, 1, 0, 5))
+        );
+        assert_eq!(
+            snooze_until(epoch(2024, 2, 28, 23, 50), 15),
+            Some(epoch(2024, 2, 29, 0, 5))
+        );
+        // end of the year and the longest snooze
+        assert_eq!(
+            snooze_until(epoch(2023, 12, 31, 22, 0), 255),
+            Some(epoch(2024, 1, 1, 2, 15))
+        );
+        assert_eq!(snooze_until(u32::MAX - 10, 1), None);
+    }
+
+    #[test]
+    fn snoozed_alarm_rings_again() {
+        let mut alarms = Alarms::new();
+        alarms
+            .add(Alarm::repeating(23, 55, DayMask::EVERY_DAY), NOW)
+            .unwrap();
+        let ring = epoch(2023, 4, 11, 23, 55);
+        assert_eq!(alarms.check(ring), Some(0));
+        assert_eq!(alarms.ringing(), Some(0));
+        let until = snooze_until(ring, 9).unwrap();
+        alarms.snooze(0, until).unwrap();
+        assert_eq!(alarms.ringing(), None);
+        assert_eq!(alarms.next_trigger(), Some((0, until)));
+        // the next day it rings at the usual time
+        let fired: std::vec::Vec<u32> = (ring..=ring + SECONDS_PER_DAY)
+            .filter(|&now| alarms.check(now).is_some())
+            .collect();
+        assert_eq!(fired, [until, ring + SECONDS_PER_DAY]);
+        assert_eq!(alarms.ringing(), Some(0));
+        alarms.dismiss();
+        assert_eq!(alarms.ringing(), None);
+    }
+
+    #[test]
+    fn dismiss_forgets_the_snooze() {
+        let mut alarms = Alarms::new();
+        alarms.add(Alarm::new(22, 20), NOW).unwrap();
+        assert_eq!(alarms.snooze(1, NOW + 60), None);
+        alarms.snooze(0, NOW + 60).unwrap();
+        assert_eq!(alarms.next_trigger(), Some((0, NOW + 60)));
+        alarms.dismiss();
+        assert_eq!(alarms.snoozed(), None);
+        assert_eq!(alarms.next_trigger(), Some((0, epoch(2023, 4, 11, 22, 20))));
+        // a late snooze does not ring
+        alarms.snooze(0, NOW + 10).unwrap();
+        assert_eq!(alarms.check(NOW + 10 + GRACE_SECONDS), None);
+        assert_eq!(alarms.snoozed(), None);
+    }
+
+    #[test]
+    fn ramp_of_the_next_alarm() {
+        use crate::ramp::{Curve, FULL_LEVEL};
+
+        let mut alarms = Alarms::new();
+        let ring = epoch(2023, 4, 11, 22, 30);
+        alarms
+            .add(
+                Alarm::new(22, 30).with_ramp(Ramp::new(10, Curve::Linear)),
+                NOW,
+            )
+            .unwrap();
+        // 22:18, before the ramp it only wakes up at the start
+        assert_eq!(alarms.ramp(NOW), None);
+        assert_eq!(alarms.next_wake_up(NOW), Some(ring - 600));
+        assert_eq!(alarms.ramp(ring - 600).map(|l| l.light), Some(0));
+        assert_eq!(alarms.next_wake_up(ring - 600), Some(ring));
+        assert_eq!(alarms.ramp(ring - 1).map(|l| l.light), Some(FULL_LEVEL));
+        // after ringing the one shot alarm has nothing more
+        assert_eq!(alarms.check(ring), Some(0));
+        assert_eq!(alarms.ramp(ring), None);
+        assert_eq!(alarms.next_wake_up(ring), None);
+    }
+
+    #[test]
+    fn snoozed_alarm_has_no_ramp() {
+        use crate::ramp::Curve;
+
+        let mut alarms = Alarms::new();
+        alarms
+            .add(
+                Alarm::new(22, 30).with_ramp(Ramp::new(10, Curve::Linear)),
+                NOW,
+            )
+            .unwrap();
+        alarms.snooze(0, NOW + 60).unwrap();
+        assert_eq!(alarms.ramp(NOW + 30), None);
+        assert_eq!(alarms.next_wake_up(NOW), Some(NOW + 60));
+        // without ramp the wake up is the trigger
+        alarms.dismiss();
+        alarms.set(0, Alarm::new(22, 30), NOW).unwrap();
+        assert_eq!(alarms.ramp(NOW + 700), None);
+        assert_eq!(alarms.next_wake_up(NOW), Some(epoch(2023, 4, 11, 22, 30)));
+    }
+
+    #[test]
+    fn alarm_in_local_time() {
+        let buenos_aires = TimeZone::new(-180, DstRule::None);
+        // 22:18 UTC is 19:18 in Buenos Aires
+        assert_eq!(
+            Alarm::new(20, 0).next_trigger(NOW, &buenos_aires),
+            Some(epoch(2023, 4, 11, 23, 0))
+        );
+        let mut alarms = Alarms::new();
+        alarms.add(Alarm::new(7, 0), NOW).unwrap();
+        alarms.set_zone(buenos_aires, NOW);
+        assert_eq!(alarms.next_trigger(), Some((0, epoch(2023, 4, 12, 10, 0))));
+    }
+
+    #[test]
+    fn alarm_across_the_dst_transitions() {
+        let cet = TimeZone::new(60, DstRule::Eu);
+        let every_day = Alarm::repeating(2, 30, DayMask::EVERY_DAY);
+        // the 02:30 of the 2023-03-26 does not exist, it rings one hour later
+        let before = epoch(2023, 3, 25, 12, 0);
+        assert_eq!(
+            every_day.next_trigger(before, &cet),
+            Some(epoch(2023, 3, 26, 1, 30))
+        );
+        // the 02:30 of the 2023-10-29 happens twice, it rings only the first time
+        let before = epoch(2023, 10, 28, 12, 0);
+        let first = epoch(2023, 10, 29, 0, 30);
+        assert_eq!(every_day.next_trigger(before, &cet), Some(first));
+        assert_eq!(
+            every_day.next_trigger(first, &cet),
+            Some(epoch(2023, 10, 30, 1, 30))
+        );
+        // and the rest of the days it is always the same local time
+        let mut alarms = Alarms::new();
+        alarms.set_zone(cet, before);
+        alarms
+            .add(Alarm::repeating(7, 0, DayMask::EVERY_DAY), before)
+            .unwrap();
+        let fired: std::vec::Vec<u32> = (before..before + 3 * SECONDS_PER_DAY)
+            .step_by(30)
+            .filter(|&now| alarms.check(now).is_some())
+            .collect();
+        // the summer time ends on the 29th, from there 07:00 CET is 06:00 UTC
+        assert_eq!(
+            fired,
+            [
+                epoch(2023, 10, 29, 6, 0),
+                epoch(2023, 10, 30, 6, 0),
+                epoch(2023, 10, 31, 6, 0)
+            ]
+        );
+        for (day, &now) in (29..).zip(fired.iter()) {
+            let local = cet.datetime(now);
+            assert_eq!((local.day, local.hour, local.min), (day, 7, 0));
+        }
+    }
+
+    #[test]
+    fn alarm_text_in_each_language() {
+        use crate::locale::SPANISH;
+        let text = |alarm: Alarm, locale| {
+            let mut text: heapless::String<32> = heapless::String::new();
+            alarm.write(&mut text, locale).unwrap();
+            text
+        };
+        let weekdays = Alarm::repeating(6, 45, DayMask::WEEKDAYS);
+        assert_eq!(text(weekdays, &ENGLISH), "06:45 MTWTF--");
+        assert_eq!(text(weekdays, &SPANISH), "06:45 LMXJV--");
+        assert_eq!(text(Alarm::new(7, 0), &SPANISH), "07:00 una vez");
+        let off = Alarm {
+            enabled: false,
+            ..weekdays
+        };
+        assert_eq!(text(off, &SPANISH), "06:45 apagada");
+    }
+}
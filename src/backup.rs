@@ -0,0 +1,221 @@
+/// Settings that survive the resets in the battery backed registers
+use crate::alarm::{Alarm, Alarms, MAX_ALARMS};
+use crate::datetime::DayMask;
+use crate::ramp::{Curve, Ramp};
+use stm32f1xx_hal::backup_domain::BackupDomain;
+
+/// 16 bits registers that keep their values while there is power in VBAT
+pub trait BackupRegisters {
+    /// number of registers available
+    const LEN: usize;
+
+    fn read(&self, index: usize) -> u16;
+    fn write(&mut self, index: usize, value: u16);
+}
+
+// the medium density devices (stm32f103c8) only have the ten low
+// data registers
+impl BackupRegisters for BackupDomain {
+    const LEN: usize = 10;
+
+    fn read(&self, index: usize) -> u16 {
+        self.read_data_register_low(index)
+    }
+
+    fn write(&mut self, index: usize, value: u16) {
+        self.write_data_register_low(index, value)
+    }
+}
+
+//-------------------------------------------------------------------------
+//                        registers layout
+//-------------------------------------------------------------------------
+// | 0: MAGIC << 8 | VERSION | 1: number of alarms | 2..: two registers per alarm |
+// each alarm is | hour << 8 | min | ramp minutes << 10 | exponential << 9 | enabled << 8 | days |
+const MARKER_REGISTER: usize = 0;
+const ALARMS_LEN_REGISTER: usize = 1;
+const ALARMS_REGISTER: usize = 2;
+const REGISTERS_PER_ALARM: usize = 2;
+// 'W' of waker
+const MAGIC: u16 = 0x57;
+// increment this every time that the layout changes
+const VERSION: u16 = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BootState {
+    /// the backup domain was reset (first power up or battery loss), the RTC time is garbage
+    Unconfigured,
+    /// the RTC is running but the settings were saved with another layout version
+    Outdated,
+    /// the RTC time and the settings are valid
+    Configured,
+}
+
+pub struct Backup<B> {
+    registers: B,
+}
+
+impl<B: BackupRegisters> Backup<B> {
+    pub fn new(registers: B) -> Self {
+        assert!(ALARMS_REGISTER + MAX_ALARMS * REGISTERS_PER_ALARM <= B::LEN);
+        Self { registers }
+    }
+
+    pub fn boot_state(&self) -> BootState {
+        let marker = self.registers.read(MARKER_REGISTER);
+        match (marker >> 8, marker & 0xff) {
+            (MAGIC, VERSION) => BootState::Configured,
+            (MAGIC, _) => BootState::Outdated,
+            _ => BootState::Unconfigured,
+        }
+    }
+
+    /// write the marker of the actual version, call it after seeding the RTC and the settings
+    pub fn mark_configured(&mut self) {
+        self.registers.write(MARKER_REGISTER, MAGIC << 8 | VERSION);
+    }
+
+    pub fn save_alarms(&mut self, alarms: &Alarms) {
+        self.registers
+            .write(ALARMS_LEN_REGISTER, alarms.len() as u16);
+        for (index, alarm) in alarms.iter().enumerate() {
+            let [time, flags] = encode(alarm);
+            let register = ALARMS_REGISTER + index * REGISTERS_PER_ALARM;
+            self.registers.write(register, time);
+            self.registers.write(register + 1, flags);
+        }
+    }
+
+    /// the saved alarms armed from `now`, `None` if the settings are not valid
+    pub fn load_alarms(&self, now: u32) -> Option<Alarms> {
+        if self.boot_state() != BootState::Configured {
+            return None;
+        }
+        let len = usize::from(self.registers.read(ALARMS_LEN_REGISTER));
+        if len > MAX_ALARMS {
+            return None;
+        }
+        let mut alarms = Alarms::new();
+        for index in 0..len {
+            let register = ALARMS_REGISTER + index * REGISTERS_PER_ALARM;
+            let alarm = decode([
+                self.registers.read(register),
+                self.registers.read(register + 1),
+            ])?;
+            alarms.add(alarm, now).ok()?;
+        }
+        Some(alarms)
+    }
+}
+
+fn encode(alarm: &Alarm) -> [u16; REGISTERS_PER_ALARM] {
+    [
+        u16::from(alarm.hour) << 8 | u16::from(alarm.min),
+        u16::from(alarm.ramp.minutes) << 10
+            | u16::from(alarm.ramp.curve == Curve::Exponential) << 9
+            | u16::from(alarm.enabled) << 8
+            | u16::from(alarm.repeat.bits()),
+    ]
+}
+
+fn decode([time, flags]: [u16; REGISTERS_PER_ALARM]) -> Option<Alarm> {
+    let (hour, min) = ((time >> 8) as u8, time as u8);
+    let minutes = (flags >> 10) as u8;
+    if hour > 23 || min > 59 || minutes > Ramp::MAX_MINUTES {
+        return None;
+    }
+    let curve = match flags >> 9 & 1 {
+        0 => Curve::Linear,
+        _ => Curve::Exponential,
+    };
+    Some(Alarm {
+        hour,
+        min,
+        enabled: flags >> 8 & 1 == 1,
+        repeat: DayMask::from_bits(flags as u8),
+        ramp: Ramp::new(minutes, curve),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::datetime::fixture::NOW;
+
+    /// in memory fake of the backup registers
+    struct FakeRegisters([u16; 10]);
+
+    impl BackupRegisters for FakeRegisters {
+        const LEN: usize = 10;
+
+        fn read(&self, index: usize) -> u16 {
+            self.0[index]
+        }
+
+        fn write(&mut self, index: usize, value: u16) {
+            self.0[index] = value
+        }
+    }
+
+    #[test]
+    fn reset_backup_domain_is_unconfigured() {
+        let mut backup = Backup::new(FakeRegisters([0; 10]));
+        assert_eq!(backup.boot_state(), BootState::Unconfigured);
+        assert!(backup.load_alarms(NOW).is_none());
+        backup.mark_configured();
+        assert_eq!(backup.boot_state(), BootState::Configured);
+        // nothing saved yet is a valid empty list
+        assert_eq!(backup.load_alarms(NOW).map(|a| a.len()), Some(0));
+    }
+
+    #[test]
+    fn other_version_is_outdated() {
+        let mut registers = FakeRegisters([0; 10]);
+        registers.0[MARKER_REGISTER] = MAGIC << 8 | (VERSION + 1);
+        let backup = Backup::new(registers);
+        assert_eq!(backup.boot_state(), BootState::Outdated);
+        assert!(backup.load_alarms(NOW).is_none());
+    }
+
+    #[test]
+    fn alarms_round_trip() {
+        let mut backup = Backup::new(FakeRegisters([0; 10]));
+        backup.mark_configured();
+        let mut alarms = Alarms::new();
+        alarms
+            .add(Alarm::repeating(7, 30, DayMask::WEEKDAYS), NOW)
+            .unwrap();
+        let mut off = Alarm::new(23, 59);
+        off.enabled = false;
+        alarms.add(off, NOW).unwrap();
+        alarms.add(Alarm::new(0, 0), NOW).unwrap();
+        let ramp = Ramp::new(Ramp::MAX_MINUTES, Curve::Exponential);
+        alarms
+            .add(
+                Alarm::repeating(9, 5, DayMask::WEEKEND).with_ramp(ramp),
+                NOW,
+            )
+            .unwrap();
+        backup.save_alarms(&alarms);
+
+        let loaded = backup.load_alarms(NOW).unwrap();
+        assert!(loaded.iter().eq(alarms.iter()));
+        assert_eq!(loaded.next_trigger(), alarms.next_trigger());
+    }
+
+    #[test]
+    fn corrupted_alarms_are_rejected() {
+        let mut backup = Backup::new(FakeRegisters([0; 10]));
+        backup.mark_configured();
+        let mut alarms = Alarms::new();
+        alarms.add(Alarm::new(7, 30), NOW).unwrap();
+        backup.save_alarms(&alarms);
+        backup.registers.write(ALARMS_REGISTER, 24 << 8);
+        assert!(backup.load_alarms(NOW).is_none());
+        backup.registers.write(ALARMS_REGISTER, 7 << 8);
+        backup.registers.write(ALARMS_REGISTER + 1, 61 << 10);
+        assert!(backup.load_alarms(NOW).is_none());
+        backup.registers.write(ALARMS_LEN_REGISTER, 5);
+        assert!(backup.load_alarms(NOW).is_none());
+    }
+}
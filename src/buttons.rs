@@ -8,50 +8,789 @@ pub enum PinState {
     Nothing,
 }
 
-type Counter = u8;
+/// High level events of a button, generated by `Button::event`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// the button was just pressed
+    Press,
+    /// short press and release (sent after the double click window, if it is enabled)
+    Click,
+    /// second press inside the double click window
+    DoubleClick,
+    /// the button has been held for `Timing::long_press` ticks
+    LongPress,
+    /// the button is still held, sent at an accelerating rate
+    Repeat,
+    Nothing,
+}
+
+/// Gesture timings of a button, all expressed in poll ticks, a zero disables the gesture
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Timing {
+    pub long_press: u16,
+    /// maximum ticks between the release and the second press of a double click
+    pub double_click: u16,
+    /// ticks held before the first `Repeat`
+    pub repeat_delay: u16,
+    /// ticks between the first repeats, it is reduced on every repeat until `repeat_min`
+    pub repeat_interval: u16,
+    pub repeat_min: u16,
+}
+
+type Ticks = u16;
+
+//-------------------------------------------------------------------------
+//                        debounce algorithms
+//-------------------------------------------------------------------------
+/// A debounce algorithm, it is fed on every poll with `true` if the pin reads as pressed
+pub trait Debounce {
+    /// returns the new stable state of the button when it changes
+    fn update(&mut self, level: bool) -> Option<bool>;
+    /// `true` if there is no change of state in progress
+    fn is_stable(&self) -> bool;
+}
+
+/// Counts the consecutive samples that differ from the stable level
+#[derive(Copy, Clone)]
+pub struct Counter {
+    threshold: u8,
+    counter: u8,
+    stable: bool,
+}
+
+impl Counter {
+    pub fn new(threshold: u8) -> Self {
+        Self {
+            threshold,
+            counter: 0,
+            stable: false,
+        }
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self::new(COUNTER_THRESOLD)
+    }
+}
+
+impl Debounce for Counter {
+    fn update(&mut self, level: bool) -> Option<bool> {
+        if level == self.stable {
+            self.counter = 0;
+            return None;
+        }
+        self.counter += 1;
+        if self.counter >= self.threshold {
+            self.counter = 0;
+            self.stable = level;
+            Some(level)
+        } else {
+            None
+        }
+    }
+
+    fn is_stable(&self) -> bool {
+        self.counter == 0
+    }
+}
+
+/// Integrates the samples between zero and `max`, the level changes when a limit is reached
+/// so a noisy pin only delays the change instead of restarting it
+#[derive(Copy, Clone)]
+pub struct Integrator {
+    max: u8,
+    integral: u8,
+    stable: bool,
+}
+
+impl Integrator {
+    pub fn new(max: u8) -> Self {
+        Self {
+            max,
+            integral: 0,
+            stable: false,
+        }
+    }
+}
+
+impl Debounce for Integrator {
+    fn update(&mut self, level: bool) -> Option<bool> {
+        self.integral = if level {
+            (self.integral + 1).min(self.max)
+        } else {
+            self.integral.saturating_sub(1)
+        };
+        match (self.integral, self.stable) {
+            (0, true) => self.stable = false,
+            (integral, false) if integral == self.max => self.stable = true,
+            _ => return None,
+        }
+        Some(self.stable)
+    }
+
+    fn is_stable(&self) -> bool {
+        self.integral == if self.stable { self.max } else { 0 }
+    }
+}
+
+/// Keeps the last `len` (up to 32) samples, the level changes when all of them are equal
+#[derive(Copy, Clone)]
+pub struct ShiftRegister {
+    mask: u32,
+    history: u32,
+    stable: bool,
+}
+
+impl ShiftRegister {
+    pub fn new(len: u8) -> Self {
+        let mask = u32::MAX >> (32 - u32::from(len.clamp(1, 32)));
+        Self {
+            mask,
+            history: 0,
+            stable: false,
+        }
+    }
+}
+
+impl Debounce for ShiftRegister {
+    fn update(&mut self, level: bool) -> Option<bool> {
+        self.history = (self.history << 1 | u32::from(level)) & self.mask;
+        match (self.history, self.stable) {
+            (0, true) => self.stable = false,
+            (history, false) if history == self.mask => self.stable = true,
+            _ => return None,
+        }
+        Some(self.stable)
+    }
+
+    fn is_stable(&self) -> bool {
+        self.history == if self.stable { self.mask } else { 0 }
+    }
+}
+
+/// Milliseconds from a monotonic clock
+pub type Millis = u32;
 
+/// The level changes when it has been different from the stable one during `window` ms, the
+/// time is read with `clock` (e.g the RTIC monotonic) so it does not depend on the poll period
 #[derive(Copy, Clone)]
-enum ButtonState {
-    High(Counter),
-    Low(Counter),
+pub struct Timed {
+    clock: fn() -> Millis,
+    window: Millis,
+    since: Option<Millis>,
+    stable: bool,
+}
+
+impl Timed {
+    pub fn new(clock: fn() -> Millis, window: Millis) -> Self {
+        Self {
+            clock,
+            window,
+            since: None,
+            stable: false,
+        }
+    }
+}
+
+impl Debounce for Timed {
+    fn update(&mut self, level: bool) -> Option<bool> {
+        if level == self.stable {
+            self.since = None;
+            return None;
+        }
+        let now = (self.clock)();
+        let since = *self.since.get_or_insert(now);
+        if now.wrapping_sub(since) >= self.window {
+            self.since = None;
+            self.stable = level;
+            Some(level)
+        } else {
+            None
+        }
+    }
+
+    fn is_stable(&self) -> bool {
+        self.since.is_none()
+    }
+}
+
+/// Which debounce algorithm the buttons use and its parameter, the one of the `DEBOUNCE` const
+/// of the main or a `debounce` command of the console
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DebounceConfig {
+    /// consecutive samples, see `Counter`
+    Counter(u8),
+    /// the maximum of the integral, see `Integrator`
+    Integrator(u8),
+    /// samples in the register, see `ShiftRegister`
+    ShiftRegister(u8),
+    /// milliseconds of the window, see `Timed`
+    Timed(Millis),
 }
 
-pub struct Button<P> {
+impl DebounceConfig {
+    /// the `clock` is only read by `Timed`
+    pub fn debouncer(self, clock: fn() -> Millis) -> Debouncer {
+        match self {
+            DebounceConfig::Counter(threshold) => Debouncer::Counter(Counter::new(threshold)),
+            DebounceConfig::Integrator(max) => Debouncer::Integrator(Integrator::new(max)),
+            DebounceConfig::ShiftRegister(len) => Debouncer::ShiftRegister(ShiftRegister::new(len)),
+            DebounceConfig::Timed(window) => Debouncer::Timed(Timed::new(clock, window)),
+        }
+    }
+}
+
+/// Any of the debounce algorithms, the one of a `DebounceConfig`
+#[derive(Copy, Clone)]
+pub enum Debouncer {
+    Counter(Counter),
+    Integrator(Integrator),
+    ShiftRegister(ShiftRegister),
+    Timed(Timed),
+}
+
+impl Debounce for Debouncer {
+    fn update(&mut self, level: bool) -> Option<bool> {
+        match self {
+            Debouncer::Counter(debounce) => debounce.update(level),
+            Debouncer::Integrator(debounce) => debounce.update(level),
+            Debouncer::ShiftRegister(debounce) => debounce.update(level),
+            Debouncer::Timed(debounce) => debounce.update(level),
+        }
+    }
+
+    fn is_stable(&self) -> bool {
+        match self {
+            Debouncer::Counter(debounce) => debounce.is_stable(),
+            Debouncer::Integrator(debounce) => debounce.is_stable(),
+            Debouncer::ShiftRegister(debounce) => debounce.is_stable(),
+            Debouncer::Timed(debounce) => debounce.is_stable(),
+        }
+    }
+}
+
+//-------------------------------------------------------------------------
+//                        button
+//-------------------------------------------------------------------------
+const COUNTER_THRESOLD: u8 = 15;
+
+#[derive(Copy, Clone)]
+enum Gesture {
+    Idle,
+    Held {
+        ticks: Ticks,
+        next_repeat: Ticks,
+        interval: Ticks,
+        // a long press, a repeat or a double click was already reported for this press
+        consumed: bool,
+    },
+    /// released, waiting for a possible second click
+    Released(Ticks),
+}
+
+/// The pin level when the button is pressed
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Polarity {
+    /// pull up input and a switch to ground
+    #[default]
+    ActiveLow,
+    /// pull down input and a switch to VCC, or a touch sensor
+    ActiveHigh,
+}
+
+/// A button with the debounce algorithm `D`, by default the `Counter` one
+pub struct Button<P, D = Counter> {
     typ: P,
-    state: ButtonState,
+    detector: Detector<D>,
 }
 
-// TODO(elsuizo:2021-11-26): look what is the better COUNTER_THRESOLD parameter for this
+#[cfg(test)]
 impl<P: InputPin<Error = Infallible>> Button<P> {
-    const COUNTER_THRESOLD: u8 = 15;
+    pub fn with_timing(typ: P, timing: Timing) -> Self {
+        Self::with_debounce(typ, Counter::default(), timing)
+    }
+}
 
-    pub fn new(typ: P) -> Self {
+impl<P: InputPin<Error = Infallible>, D: Debounce> Button<P, D> {
+    pub fn with_debounce(typ: P, debounce: D, timing: Timing) -> Self {
         Self {
             typ,
-            state: ButtonState::High(0u8),
+            detector: Detector::new(debounce, timing),
         }
     }
 
-    /// poll the pin and generate a debounce algorithm:
-    pub fn poll(&mut self) -> PinState {
-        use self::ButtonState::*;
-        let value = self.typ.is_high().expect("could this fail???");
-        match (&mut self.state, value) {
-            (High(counter), true) => *counter = 0,
-            (High(counter), false) => *counter += 1,
-            (Low(counter), true) => *counter += 1,
-            (Low(counter), false) => *counter = 0,
+    /// change the wiring of the button, by default it is `ActiveLow`
+    pub fn with_polarity(self, polarity: Polarity) -> Self {
+        Self {
+            detector: self.detector.with_polarity(polarity),
+            ..self
         }
-        match self.state {
-            High(counter) if counter >= Self::COUNTER_THRESOLD => {
-                self.state = Low(0);
-                PinState::PinUp
+    }
+
+    /// replace the debounce algorithm, call it while the button `is_idle` so a press is not
+    /// cut in half
+    pub fn set_debounce(&mut self, debounce: D) {
+        self.detector.set_debounce(debounce)
+    }
+
+    /// poll the pin and detect the gestures configured in the `Timing` of this button
+    pub fn event(&mut self) -> Event {
+        let high = self.read();
+        self.detector.event(high)
+    }
+
+    /// `true` if the button does not need to be polled until the next edge of the pin
+    pub fn is_idle(&self) -> bool {
+        self.detector.is_idle()
+    }
+
+    fn read(&self) -> bool {
+        self.typ.is_high().expect("could this fail???")
+    }
+}
+
+/// The debounce and the gestures detection of a button fed with the level of its pin, it is
+/// the same for a polled pin and for a pin that starts the polling from its EXTI interrupt
+pub struct Detector<D = Counter> {
+    polarity: Polarity,
+    debounce: D,
+    timing: Timing,
+    gesture: Gesture,
+}
+
+impl<D: Debounce> Detector<D> {
+    pub fn new(debounce: D, timing: Timing) -> Self {
+        Self {
+            polarity: Polarity::ActiveLow,
+            debounce,
+            timing,
+            gesture: Gesture::Idle,
+        }
+    }
+
+    pub fn with_polarity(self, polarity: Polarity) -> Self {
+        Self { polarity, ..self }
+    }
+
+    pub fn set_debounce(&mut self, debounce: D) {
+        self.debounce = debounce;
+    }
+
+    /// released, without a debounce in progress and without gestures waiting for time
+    pub fn is_idle(&self) -> bool {
+        matches!(self.gesture, Gesture::Idle) && self.debounce.is_stable()
+    }
+
+    /// debounce the pin level `high`
+    pub fn poll(&mut self, high: bool) -> PinState {
+        let pressed = high == (self.polarity == Polarity::ActiveHigh);
+        match self.debounce.update(pressed) {
+            Some(true) => PinState::PinUp,
+            Some(false) => PinState::PinDown,
+            None => PinState::Nothing,
+        }
+    }
+
+    /// debounce the pin level `high` and detect the gestures
+    pub fn event(&mut self, high: bool) -> Event {
+        let timing = self.timing;
+        let pressed = |consumed| Gesture::Held {
+            ticks: 0,
+            next_repeat: timing.repeat_delay,
+            interval: timing.repeat_interval,
+            consumed,
+        };
+        match (self.poll(high), self.gesture) {
+            (PinState::PinUp, Gesture::Released(_)) => {
+                self.gesture = pressed(true);
+                Event::DoubleClick
+            }
+            (PinState::PinUp, _) => {
+                self.gesture = pressed(false);
+                Event::Press
+            }
+            (PinState::PinDown, Gesture::Held { consumed, .. }) => {
+                if consumed {
+                    self.gesture = Gesture::Idle;
+                    Event::Nothing
+                } else if timing.double_click == 0 {
+                    self.gesture = Gesture::Idle;
+                    Event::Click
+                } else {
+                    self.gesture = Gesture::Released(0);
+                    Event::Nothing
+                }
             }
-            Low(counter) if counter >= Self::COUNTER_THRESOLD => {
-                self.state = High(0);
-                PinState::PinDown
+            (PinState::PinDown, _) => Event::Nothing,
+            (PinState::Nothing, Gesture::Released(ticks)) => {
+                if ticks + 1 >= timing.double_click {
+                    self.gesture = Gesture::Idle;
+                    Event::Click
+                } else {
+                    self.gesture = Gesture::Released(ticks + 1);
+                    Event::Nothing
+                }
             }
-            _ => PinState::Nothing,
+            (
+                PinState::Nothing,
+                Gesture::Held {
+                    ticks,
+                    next_repeat,
+                    interval,
+                    consumed,
+                },
+            ) => {
+                let ticks = ticks.saturating_add(1);
+                let (event, next_repeat, interval) =
+                    if timing.long_press != 0 && ticks == timing.long_press {
+                        (Event::LongPress, next_repeat, interval)
+                    } else if timing.repeat_delay != 0 && ticks >= next_repeat {
+                        // the next repeat comes a quarter sooner, up to `repeat_min`
+                        let next_repeat = ticks.saturating_add(interval.max(1));
+                        let interval = (interval - interval / 4).max(timing.repeat_min);
+                        (Event::Repeat, next_repeat, interval)
+                    } else {
+                        (Event::Nothing, next_repeat, interval)
+                    };
+                self.gesture = Gesture::Held {
+                    ticks,
+                    next_repeat,
+                    interval,
+                    consumed: consumed || event != Event::Nothing,
+                };
+                event
+            }
+            (PinState::Nothing, Gesture::Idle) => Event::Nothing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    /// a pin whose level is changed by the test according to its wiring
+    #[derive(Clone, Default)]
+    struct FakePin(Rc<Cell<bool>>, Polarity);
+
+    impl FakePin {
+        fn press(&self) {
+            self.0.set(self.1 == Polarity::ActiveHigh)
+        }
+        fn release(&self) {
+            self.0.set(self.1 == Polarity::ActiveLow)
+        }
+    }
+
+    impl InputPin for FakePin {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Self::Error> {
+            Ok(self.0.get())
+        }
+
+        fn is_low(&self) -> Result<bool, Self::Error> {
+            Ok(!self.0.get())
+        }
+    }
+
+    const TIMING: Timing = Timing {
+        long_press: 100,
+        double_click: 30,
+        repeat_delay: 0,
+        repeat_interval: 0,
+        repeat_min: 0,
+    };
+
+    fn button(timing: Timing) -> (FakePin, Button<FakePin>) {
+        button_with(Counter::default(), timing)
+    }
+
+    fn button_with<D: Debounce>(debounce: D, timing: Timing) -> (FakePin, Button<FakePin, D>) {
+        let pin = FakePin::default();
+        pin.release();
+        (pin.clone(), Button::with_debounce(pin, debounce, timing))
+    }
+
+    /// poll `ticks` times and return the events with the tick in which they happen
+    fn run<D: Debounce>(button: &mut Button<FakePin, D>, ticks: u16) -> Vec<(u16, Event)> {
+        (0..ticks)
+            .map(|tick| (tick, button.event()))
+            .filter(|&(_, event)| event != Event::Nothing)
+            .collect()
+    }
+
+    fn events<D: Debounce>(button: &mut Button<FakePin, D>, ticks: u16) -> Vec<Event> {
+        run(button, ticks)
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect()
+    }
+
+    #[test]
+    fn click_without_double_click() {
+        let (pin, mut button) = button(Timing::default());
+        pin.press();
+        assert_eq!(events(&mut button, 40), [Event::Press]);
+        pin.release();
+        assert_eq!(events(&mut button, 40), [Event::Click]);
+    }
+
+    #[test]
+    fn click_after_the_double_click_window() {
+        let (pin, mut button) = button(TIMING);
+        pin.press();
+        assert_eq!(events(&mut button, 40), [Event::Press]);
+        pin.release();
+        // debounce, then the whole window
+        assert_eq!(
+            run(&mut button, 100),
+            [(
+                COUNTER_THRESOLD as u16 + TIMING.double_click - 1,
+                Event::Click
+            )]
+        );
+    }
+
+    #[test]
+    fn double_click() {
+        let (pin, mut button) = button(TIMING);
+        pin.press();
+        events(&mut button, 20);
+        pin.release();
+        assert_eq!(events(&mut button, 20), []);
+        pin.press();
+        assert_eq!(events(&mut button, 20), [Event::DoubleClick]);
+        pin.release();
+        // no click after the double click
+        assert_eq!(events(&mut button, 100), []);
+    }
+
+    #[test]
+    fn long_press() {
+        let (pin, mut button) = button(TIMING);
+        pin.press();
+        assert_eq!(
+            run(&mut button, 200),
+            [
+                (COUNTER_THRESOLD as u16 - 1, Event::Press),
+                (
+                    COUNTER_THRESOLD as u16 - 1 + TIMING.long_press,
+                    Event::LongPress
+                )
+            ]
+        );
+        pin.release();
+        assert_eq!(events(&mut button, 100), []);
+    }
+
+    #[test]
+    fn accelerating_repeat() {
+        let timing = Timing {
+            repeat_delay: 50,
+            repeat_interval: 20,
+            repeat_min: 5,
+            ..Timing::default()
+        };
+        let (pin, mut button) = button(timing);
+        pin.press();
+        let press = COUNTER_THRESOLD as u16 - 1;
+        let ticks: Vec<u16> = run(&mut button, 200)
+            .into_iter()
+            .map(|(tick, _)| tick - press)
+            .collect();
+        // gaps of 20, 15, 12, 9, 7, 6, 5, 5...
+        assert_eq!(&ticks[..8], [0, 50, 70, 85, 97, 106, 113, 119]);
+        assert_eq!(ticks[9] - ticks[8], 5);
+        pin.release();
+        // it keeps repeating until the release is debounced, but there is no click
+        assert!(events(&mut button, 100)
+            .iter()
+            .all(|&event| event == Event::Repeat));
+    }
+
+    #[test]
+    fn bouncing_pin_is_filtered() {
+        let (pin, mut button) = button(Timing::default());
+        for _ in 0..10 {
+            pin.press();
+            assert_eq!(events(&mut button, 5), []);
+            pin.release();
+            assert_eq!(events(&mut button, 1), []);
         }
     }
+
+    /// a pin that bounces during the first 8 ticks of every transition
+    fn bounce<D: Debounce>(
+        pin: &FakePin,
+        button: &mut Button<FakePin, D>,
+        press: bool,
+    ) -> Vec<(u16, Event)> {
+        (0..100)
+            .map(|tick| {
+                let bouncing = tick < 8 && tick % 3 == 1;
+                if press != bouncing {
+                    pin.press()
+                } else {
+                    pin.release()
+                }
+                (tick, button.event())
+            })
+            .filter(|&(_, event)| event != Event::Nothing)
+            .collect()
+    }
+
+    #[test]
+    fn active_high_polarity() {
+        let pin = FakePin(Rc::default(), Polarity::ActiveHigh);
+        pin.release();
+        assert!(!pin.is_high().unwrap());
+        let mut button =
+            Button::with_timing(pin.clone(), TIMING).with_polarity(Polarity::ActiveHigh);
+        assert_eq!(events(&mut button, 40), []);
+        pin.press();
+        assert_eq!(events(&mut button, 40), [Event::Press]);
+        pin.release();
+        assert_eq!(events(&mut button, 100), [Event::Click]);
+    }
+
+    #[test]
+    fn wrong_polarity_is_always_pressed() {
+        // a pull down pin read as active low looks pressed while it is released
+        let pin = FakePin(Rc::default(), Polarity::ActiveHigh);
+        pin.release();
+        let mut button = Button::with_timing(pin.clone(), TIMING);
+        assert_eq!(events(&mut button, 40), [Event::Press]);
+    }
+
+    #[test]
+    fn idle_only_when_nothing_is_pending() {
+        let (pin, mut button) = button(TIMING);
+        assert!(button.is_idle());
+        pin.press();
+        button.event();
+        // debouncing
+        assert!(!button.is_idle());
+        events(&mut button, 40);
+        // held
+        assert!(!button.is_idle());
+        pin.release();
+        events(&mut button, COUNTER_THRESOLD as u16);
+        // waiting for a double click
+        assert!(!button.is_idle());
+        assert_eq!(events(&mut button, 100), [Event::Click]);
+        assert!(button.is_idle());
+    }
+
+    #[test]
+    fn detector_without_pin() {
+        let mut detector = Detector::new(Counter::new(2), Timing::default());
+        let events: Vec<Event> = [true, false, false, false, true, true, true]
+            .iter()
+            .map(|&high| detector.event(high))
+            .collect();
+        use Event::*;
+        assert_eq!(
+            events,
+            [Nothing, Nothing, Press, Nothing, Nothing, Click, Nothing]
+        );
+        assert!(detector.is_idle());
+    }
+
+    #[test]
+    fn integrator_debounce() {
+        let (pin, mut button) = button_with(Integrator::new(10), Timing::default());
+        // it never reaches zero with a short noise
+        for _ in 0..20 {
+            pin.press();
+            assert_eq!(events(&mut button, 9), []);
+            pin.release();
+            assert_eq!(events(&mut button, 9), []);
+        }
+        // it goes down 1 and up 2 on every cycle of the bounce
+        assert_eq!(bounce(&pin, &mut button, true), [(15, Event::Press)]);
+        assert_eq!(bounce(&pin, &mut button, false), [(15, Event::Click)]);
+    }
+
+    #[test]
+    fn shift_register_debounce() {
+        let (pin, mut button) = button_with(ShiftRegister::new(8), Timing::default());
+        // the last bounce is in the tick 7
+        assert_eq!(bounce(&pin, &mut button, true), [(15, Event::Press)]);
+        assert_eq!(bounce(&pin, &mut button, false), [(15, Event::Click)]);
+        pin.press();
+        assert_eq!(events(&mut button, 7), []);
+        assert_eq!(events(&mut button, 1), [Event::Press]);
+    }
+
+    std::thread_local! {
+        static NOW: Cell<Millis> = const { Cell::new(0) };
+    }
+
+    fn now() -> Millis {
+        NOW.with(|now| now.get())
+    }
+
+    fn advance(button: &mut Button<FakePin, Timed>, ms: Millis) -> Vec<Event> {
+        (0..ms)
+            .map(|_| {
+                NOW.with(|now| now.set(now.get() + 1));
+                button.event()
+            })
+            .filter(|&event| event != Event::Nothing)
+            .collect()
+    }
+
+    #[test]
+    fn timed_debounce() {
+        let (pin, mut button) = button_with(Timed::new(now, 20), Timing::default());
+        pin.press();
+        assert_eq!(advance(&mut button, 20), []);
+        assert_eq!(advance(&mut button, 1), [Event::Press]);
+        // the poll period does not matter, only the time
+        pin.release();
+        assert_eq!(button.event(), Event::Nothing);
+        NOW.with(|now| now.set(now.get() + 19));
+        assert_eq!(button.event(), Event::Nothing);
+        NOW.with(|now| now.set(now.get() + 1));
+        assert_eq!(button.event(), Event::Click);
+        // a bounce restarts the window
+        pin.press();
+        assert_eq!(advance(&mut button, 10), []);
+        pin.release();
+        assert_eq!(advance(&mut button, 1), []);
+        pin.press();
+        assert_eq!(advance(&mut button, 20), []);
+        assert_eq!(advance(&mut button, 1), [Event::Press]);
+    }
+
+    #[test]
+    fn debounce_from_the_config() {
+        let (pin, mut button) = button_with(
+            DebounceConfig::ShiftRegister(8).debouncer(now),
+            Timing::default(),
+        );
+        pin.press();
+        assert_eq!(events(&mut button, 7), []);
+        assert_eq!(events(&mut button, 1), [Event::Press]);
+        let (pin, mut button) = button_with(
+            DebounceConfig::Counter(15).debouncer(now),
+            Timing::default(),
+        );
+        pin.press();
+        assert_eq!(events(&mut button, 14), []);
+        assert_eq!(events(&mut button, 1), [Event::Press]);
+        // and another one while the button is idle
+        pin.release();
+        events(&mut button, 100);
+        assert!(button.is_idle());
+        button.set_debounce(DebounceConfig::Integrator(3).debouncer(now));
+        pin.press();
+        assert_eq!(events(&mut button, 2), []);
+        assert_eq!(events(&mut button, 1), [Event::Press]);
+    }
 }
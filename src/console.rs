@@ -0,0 +1,400 @@
+/// A line oriented command shell over the serial port, the bytes are buffered here and the
+/// lines are parsed into a `Command` without allocations
+use crate::alarm::Alarm;
+use crate::buttons::DebounceConfig;
+use crate::datetime::{DateTime, DayMask, DayOfWeek, ParseError};
+use crate::io::Level;
+use core::convert::TryFrom;
+use heapless::String;
+
+/// the longest command line, the longer ones are discarded
+pub const LINE_LEN: usize = 48;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+pub const HELP: &str = "time get | time set <iso> | alarm list | alarm add <HH:MM> [once|daily|weekdays|weekend|MTWTF--] | alarm del <n> | bright <0-255> | debounce <counter|integrator|shift|timed> <n> | log <debug|info|warn|error> | reboot";
+
+/// The bytes that arrive from the serial port until the end of a line
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    line: String<LINE_LEN>,
+    overflow: bool,
+}
+
+/// Why a line was discarded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineError {
+    /// more than `LINE_LEN` bytes
+    TooLong,
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        Self {
+            line: String::new(),
+            overflow: false,
+        }
+    }
+
+    /// add a byte, at the end of a line (`\r` or `\n`) returns it, the empty lines are skipped
+    pub fn push(&mut self, byte: u8) -> Option<Result<String<LINE_LEN>, LineError>> {
+        match byte {
+            b'\r' | b'\n' => {
+                let line = core::mem::take(&mut self.line);
+                if core::mem::take(&mut self.overflow) {
+                    Some(Err(LineError::TooLong))
+                } else if line.is_empty() {
+                    None
+                } else {
+                    Some(Ok(line))
+                }
+            }
+            BACKSPACE | DELETE => {
+                self.line.pop();
+                None
+            }
+            // the control characters and the non ASCII bytes are ignored
+            b' '..=b'~' => {
+                if self.line.push(char::from(byte)).is_err() {
+                    self.overflow = true;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    TimeGet,
+    /// the local date and time
+    TimeSet(DateTime),
+    AlarmList,
+    AlarmAdd(Alarm),
+    /// the index in the `Alarms`, the user counts from one
+    AlarmDel(usize),
+    /// the contrast of the display
+    Bright(u8),
+    /// the debounce of the buttons, to tune it for noisy switches
+    Debounce(DebounceConfig),
+    /// the minimum level of the logs
+    LogLevel(Level),
+    Reboot,
+}
+
+/// Why a line is not a `Command`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// the first words are not a command
+    Unknown,
+    /// the command needs more arguments
+    MissingArgument,
+    /// there are more arguments than what the command needs
+    ExtraArgument,
+    /// an index or the brightness is not a number in its range
+    Number,
+    /// the time of an alarm is not `HH:MM`
+    AlarmTime,
+    /// the days of an alarm are not a name or a mask
+    Days,
+    /// not one of the names of the debounce algorithms
+    Debounce,
+    /// not one of the names of the log levels
+    Level,
+    /// the date and time of `time set`
+    Time(ParseError),
+}
+
+impl From<ParseError> for CommandError {
+    fn from(error: ParseError) -> Self {
+        CommandError::Time(error)
+    }
+}
+
+impl ::core::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
+        match self {
+            CommandError::Unknown => write!(f, "unknown command, try help"),
+            CommandError::MissingArgument => write!(f, "missing argument"),
+            CommandError::ExtraArgument => write!(f, "too many arguments"),
+            CommandError::Number => write!(f, "invalid number"),
+            CommandError::AlarmTime => write!(f, "the alarm time is not HH:MM"),
+            CommandError::Days => write!(f, "invalid days"),
+            CommandError::Debounce => write!(f, "invalid debounce"),
+            CommandError::Level => write!(f, "invalid log level"),
+            CommandError::Time(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Command {
+    /// `now` is the local time, `time set` takes the date from it when only the time is given
+    pub fn parse(line: &str, now: &DateTime) -> Result<Command, CommandError> {
+        let mut words = line.split_ascii_whitespace();
+        let command = match (words.next(), words.next()) {
+            (Some("help"), None) => return Ok(Command::Help),
+            (Some("reboot"), None) => return Ok(Command::Reboot),
+            (Some("bright"), Some(level)) => {
+                Command::Bright(level.parse().map_err(|_| CommandError::Number)?)
+            }
+            (Some("debounce"), Some(name)) => {
+                let value = words.next().ok_or(CommandError::MissingArgument)?;
+                Command::Debounce(parse_debounce(name, value)?)
+            }
+            (Some("log"), Some(level)) => {
+                Command::LogLevel(Level::from_name(level).ok_or(CommandError::Level)?)
+            }
+            (Some("time"), Some("get")) => Command::TimeGet,
+            (Some("time"), Some("set")) => {
+                let text = words.next().ok_or(CommandError::MissingArgument)?;
+                Command::TimeSet(now.with_iso(text)?)
+            }
+            (Some("alarm"), Some("list")) => Command::AlarmList,
+            (Some("alarm"), Some("add")) => {
+                let time = words.next().ok_or(CommandError::MissingArgument)?;
+                let (hour, min) = parse_hour_min(time).ok_or(CommandError::AlarmTime)?;
+                let days = words.next().map_or(Some(DayMask::NONE), parse_days);
+                Command::AlarmAdd(Alarm::repeating(hour, min, days.ok_or(CommandError::Days)?))
+            }
+            (Some("alarm"), Some("del")) => {
+                let number = words.next().ok_or(CommandError::MissingArgument)?;
+                match number.parse::<usize>() {
+                    Ok(number) if number > 0 => Command::AlarmDel(number - 1),
+                    _ => return Err(CommandError::Number),
+                }
+            }
+            (Some("bright" | "debounce" | "log" | "time" | "alarm"), None) => {
+                return Err(CommandError::MissingArgument)
+            }
+            _ => return Err(CommandError::Unknown),
+        };
+        match words.next() {
+            Some(_) => Err(CommandError::ExtraArgument),
+            None => Ok(command),
+        }
+    }
+}
+
+/// the samples of `counter`, `integrator` or `shift` (up to 32), or the milliseconds of `timed`
+fn parse_debounce(name: &str, value: &str) -> Result<DebounceConfig, CommandError> {
+    let value: u32 = match value.parse() {
+        Ok(value) if value > 0 => value,
+        _ => return Err(CommandError::Number),
+    };
+    let samples = |max| match u8::try_from(value) {
+        Ok(samples) if samples <= max => Ok(samples),
+        _ => Err(CommandError::Number),
+    };
+    Ok(match name {
+        "counter" => DebounceConfig::Counter(samples(u8::MAX)?),
+        "integrator" => DebounceConfig::Integrator(samples(u8::MAX)?),
+        "shift" => DebounceConfig::ShiftRegister(samples(32)?),
+        "timed" => DebounceConfig::Timed(value),
+        _ => return Err(CommandError::Debounce),
+    })
+}
+
+/// `HH:MM` with a valid hour and minute
+fn parse_hour_min(text: &str) -> Option<(u8, u8)> {
+    let (hour, min) = text.split_once(':')?;
+    let digits = |text: &str| text.len() == 2 && text.bytes().all(|b| b.is_ascii_digit());
+    if !digits(hour) || !digits(min) {
+        return None;
+    }
+    let hour: u8 = hour.parse().ok()?;
+    let min: u8 = min.parse().ok()?;
+    (hour < 24 && min < 60).then_some((hour, min))
+}
+
+/// a name or the letter of each day from Monday (`MTWTFSS`) or `-` for a day off (e.g `MTWTF--`)
+fn parse_days(text: &str) -> Option<DayMask> {
+    match text {
+        "once" => Some(DayMask::NONE),
+        "daily" => Some(DayMask::EVERY_DAY),
+        "weekdays" => Some(DayMask::WEEKDAYS),
+        "weekend" => Some(DayMask::WEEKEND),
+        mask if mask.len() == 7 => {
+            let mut days = DayMask::NONE;
+            let mut day = DayOfWeek::Monday;
+            for (byte, letter) in mask.bytes().zip(b"MTWTFSS".iter()) {
+                match byte {
+                    b'-' => {}
+                    byte if byte == *letter => days = days.with(day),
+                    _ => return None,
+                }
+                day = day.next();
+            }
+            Some(days)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn now() -> DateTime {
+        DateTime::from_ymd_hms(2023, 4, 11, 22, 18, 5).unwrap()
+    }
+
+    fn parse(line: &str) -> Result<Command, CommandError> {
+        Command::parse(line, &now())
+    }
+
+    fn push_all(
+        buffer: &mut LineBuffer,
+        bytes: &[u8],
+    ) -> Option<Result<String<LINE_LEN>, LineError>> {
+        bytes.iter().filter_map(|&byte| buffer.push(byte)).last()
+    }
+
+    #[test]
+    fn lines_end_with_cr_or_lf() {
+        let mut buffer = LineBuffer::new();
+        assert_eq!(push_all(&mut buffer, b"time get"), None);
+        assert_eq!(buffer.push(b'\r'), Some(Ok("time get".into())));
+        // the `\n` of a `\r\n` is an empty line
+        assert_eq!(buffer.push(b'\n'), None);
+        assert_eq!(
+            push_all(&mut buffer, b"rebooo\x08t\n"),
+            Some(Ok("reboot".into()))
+        );
+        // the control characters are ignored
+        assert_eq!(
+            push_all(&mut buffer, b"\x1bhelp\xff\n"),
+            Some(Ok("help".into()))
+        );
+    }
+
+    #[test]
+    fn long_lines_are_discarded() {
+        let mut buffer = LineBuffer::new();
+        let long = [b'a'; LINE_LEN + 1];
+        assert_eq!(push_all(&mut buffer, &long), None);
+        assert_eq!(buffer.push(b'\n'), Some(Err(LineError::TooLong)));
+        // and the next one is ok
+        assert_eq!(push_all(&mut buffer, b"help\n"), Some(Ok("help".into())));
+    }
+
+    #[test]
+    fn simple_commands() {
+        assert_eq!(parse("help"), Ok(Command::Help));
+        assert_eq!(parse("  time   get "), Ok(Command::TimeGet));
+        assert_eq!(parse("alarm list"), Ok(Command::AlarmList));
+        assert_eq!(parse("reboot"), Ok(Command::Reboot));
+        assert_eq!(parse("bright 255"), Ok(Command::Bright(255)));
+        assert_eq!(parse("alarm del 2"), Ok(Command::AlarmDel(1)));
+        assert_eq!(parse("log debug"), Ok(Command::LogLevel(Level::Debug)));
+    }
+
+    #[test]
+    fn time_set() {
+        let date = |h, m, s| DateTime::from_ymd_hms(2023, 4, 11, h, m, s).unwrap();
+        assert_eq!(
+            parse("time set 2023-04-11T07:30:00"),
+            Ok(Command::TimeSet(date(7, 30, 0)))
+        );
+        // only the time keeps the date of today
+        assert_eq!(
+            parse("time set 06:00:10"),
+            Ok(Command::TimeSet(date(6, 0, 10)))
+        );
+        assert_eq!(
+            parse("time set 25:00:00"),
+            Err(CommandError::Time(ParseError::Invalid(
+                crate::datetime::DateTimeError::Hour(25)
+            )))
+        );
+        assert_eq!(parse("time set"), Err(CommandError::MissingArgument));
+    }
+
+    #[test]
+    fn alarm_add() {
+        assert_eq!(
+            parse("alarm add 07:00"),
+            Ok(Command::AlarmAdd(Alarm::new(7, 0)))
+        );
+        assert_eq!(
+            parse("alarm add 06:45 weekdays"),
+            Ok(Command::AlarmAdd(Alarm::repeating(
+                6,
+                45,
+                DayMask::WEEKDAYS
+            )))
+        );
+        assert_eq!(
+            parse("alarm add 09:30 -----SS"),
+            Ok(Command::AlarmAdd(Alarm::repeating(9, 30, DayMask::WEEKEND)))
+        );
+        assert_eq!(parse("alarm add 7:00"), Err(CommandError::AlarmTime));
+        assert_eq!(parse("alarm add +7:00"), Err(CommandError::AlarmTime));
+        assert_eq!(parse("alarm add 07:+5"), Err(CommandError::AlarmTime));
+        assert_eq!(parse("alarm add 24:00"), Err(CommandError::AlarmTime));
+        assert_eq!(parse("alarm add 07:60"), Err(CommandError::AlarmTime));
+        assert_eq!(parse("alarm add 07:00 sometimes"), Err(CommandError::Days));
+        assert_eq!(parse("alarm add 07:00 xxxxxxx"), Err(CommandError::Days));
+        assert_eq!(parse("alarm add 07:00 TMWTF--"), Err(CommandError::Days));
+        assert_eq!(parse("alarm add"), Err(CommandError::MissingArgument));
+    }
+
+    #[test]
+    fn debounce() {
+        assert_eq!(
+            parse("debounce counter 15"),
+            Ok(Command::Debounce(DebounceConfig::Counter(15)))
+        );
+        assert_eq!(
+            parse("debounce integrator 8"),
+            Ok(Command::Debounce(DebounceConfig::Integrator(8)))
+        );
+        assert_eq!(
+            parse("debounce shift 32"),
+            Ok(Command::Debounce(DebounceConfig::ShiftRegister(32)))
+        );
+        assert_eq!(
+            parse("debounce timed 300"),
+            Ok(Command::Debounce(DebounceConfig::Timed(300)))
+        );
+        assert_eq!(parse("debounce shift 33"), Err(CommandError::Number));
+        assert_eq!(parse("debounce counter 256"), Err(CommandError::Number));
+        assert_eq!(parse("debounce timed 0"), Err(CommandError::Number));
+        assert_eq!(parse("debounce magic 5"), Err(CommandError::Debounce));
+        assert_eq!(
+            parse("debounce counter"),
+            Err(CommandError::MissingArgument)
+        );
+        assert_eq!(parse("debounce"), Err(CommandError::MissingArgument));
+    }
+
+    #[test]
+    fn help_lists_commands() {
+        for usage in HELP.split(" | ") {
+            // the words before the arguments
+            let end = usage.find(['<', '[']).unwrap_or(usage.len());
+            assert_ne!(
+                parse(&usage[..end]),
+                Err(CommandError::Unknown),
+                "{}",
+                usage
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_commands() {
+        assert_eq!(parse("dance"), Err(CommandError::Unknown));
+        assert_eq!(parse("time now"), Err(CommandError::Unknown));
+        assert_eq!(parse("time"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("help me"), Err(CommandError::Unknown));
+        assert_eq!(parse("time get now"), Err(CommandError::ExtraArgument));
+        assert_eq!(parse("bright 256"), Err(CommandError::Number));
+        assert_eq!(parse("alarm del 0"), Err(CommandError::Number));
+        assert_eq!(parse("alarm del x"), Err(CommandError::Number));
+        assert_eq!(parse("bright 10 20"), Err(CommandError::ExtraArgument));
+        assert_eq!(parse("log loud"), Err(CommandError::Level));
+        assert_eq!(parse("log"), Err(CommandError::MissingArgument));
+    }
+}
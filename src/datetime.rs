@@ -1,3 +1,7 @@
+use crate::format::DateTimeFormat;
+use crate::locale::{Locale, ENGLISH};
+use core::convert::TryFrom;
+
 static DAYS_IN_MONTH: [u32; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
 static DAYS_IN_MONTH_LEAP_YEAR: [u32; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
 fn days_in_month(is_leap_year: bool) -> &'static [u32; 12] {
@@ -7,6 +11,10 @@ fn days_in_month(is_leap_year: bool) -> &'static [u32; 12] {
         &DAYS_IN_MONTH
     }
 }
+/// the number of days of `month` (1..=12) in `year`
+pub fn days_of_month(year: u16, month: u8) -> u8 {
+    days_in_month(is_leap(year))[usize::from(month.clamp(1, 12)) - 1] as u8
+}
 fn is_leap(year: u16) -> bool {
     if year % 4 != 0 {
         false
@@ -17,6 +25,41 @@ fn is_leap(year: u16) -> bool {
     }
 }
 
+// the civil calendar algorithms of Howard Hinnant
+// (http://howardhinnant.github.io/date_algorithms.html) without loops. The years start in March
+// so the leap day is the last one, and the eras are the 400 years cycles of the calendar. All is
+// unsigned because the epoch starts in 1970
+/// days from 0000-03-01 to 1970-01-01
+const DAYS_TO_EPOCH: u32 = 719468;
+const DAYS_PER_ERA: u32 = 146097;
+
+/// the `(year, month, day)` of the days since 1970-01-01
+fn civil_from_days(days: u32) -> (u16, u8, u8) {
+    let days = days + DAYS_TO_EPOCH;
+    let era = days / DAYS_PER_ERA;
+    let day_of_era = days - era * DAYS_PER_ERA;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // the month starting in March
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + u32::from(month <= 2);
+    (year as u16, month as u8, day as u8)
+}
+
+/// the days since 1970-01-01 of a valid date after it
+fn days_from_civil(year: u16, month: u8, day: u8) -> u32 {
+    let year = u32::from(year) - u32::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month = (u32::from(month) + 9) % 12;
+    let day_of_year = (153 * month + 2) / 5 + u32::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * DAYS_PER_ERA + day_of_era - DAYS_TO_EPOCH
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum DayOfWeek {
     Monday,
@@ -54,9 +97,84 @@ impl DayOfWeek {
         }
     }
 }
+/// The English name, see `Locale` for the others
 impl ::core::fmt::Display for DayOfWeek {
     fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
-        write!(f, "{:?}", self)
+        f.write_str(ENGLISH.weekday(*self))
+    }
+}
+
+/// A set of days of the week, one bit per `DayOfWeek` (Monday is the bit 0)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DayMask(u8);
+impl DayMask {
+    pub const NONE: DayMask = DayMask(0);
+    pub const WEEKDAYS: DayMask = DayMask(0b001_1111);
+    pub const WEEKEND: DayMask = DayMask(0b110_0000);
+    pub const EVERY_DAY: DayMask = DayMask(0b111_1111);
+
+    pub fn from_bits(bits: u8) -> DayMask {
+        DayMask(bits & Self::EVERY_DAY.0)
+    }
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+    pub fn with(self, day: DayOfWeek) -> DayMask {
+        DayMask(self.0 | 1 << day as u8)
+    }
+    #[cfg(test)]
+    pub fn without(self, day: DayOfWeek) -> DayMask {
+        DayMask(self.0 & !(1 << day as u8))
+    }
+    pub fn contains(self, day: DayOfWeek) -> bool {
+        self.0 & 1 << day as u8 != 0
+    }
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// one letter of `locale` per day starting on Monday and `-` for the days off
+    pub fn write<W: ::core::fmt::Write>(self, w: &mut W, locale: &Locale) -> ::core::fmt::Result {
+        let mut day = DayOfWeek::Monday;
+        for &letter in locale.day_letters.iter() {
+            w.write_char(if self.contains(day) { letter } else { '-' })?;
+            day = day.next();
+        }
+        Ok(())
+    }
+}
+/// Shows the mask as one letter per day starting on Monday: `MTWTF--`
+impl ::core::fmt::Display for DayMask {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
+        self.write(f, &ENGLISH)
+    }
+}
+
+/// The field that is not valid in a `DateTime`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DateTimeError {
+    /// before 1970
+    Year(u16),
+    Month(u8),
+    /// zero or after the last day of the month
+    Day(u8),
+    Hour(u8),
+    Minute(u8),
+    Second(u8),
+    /// after 2106-02-07 06:28:15, the end of the 32 bits epoch
+    OutOfRange,
+}
+impl ::core::fmt::Display for DateTimeError {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
+        match self {
+            DateTimeError::Year(year) => write!(f, "invalid year {}", year),
+            DateTimeError::Month(month) => write!(f, "invalid month {}", month),
+            DateTimeError::Day(day) => write!(f, "invalid day {}", day),
+            DateTimeError::Hour(hour) => write!(f, "invalid hour {}", hour),
+            DateTimeError::Minute(min) => write!(f, "invalid minute {}", min),
+            DateTimeError::Second(sec) => write!(f, "invalid second {}", sec),
+            DateTimeError::OutOfRange => write!(f, "out of the epoch range"),
+        }
     }
 }
 
@@ -72,74 +190,319 @@ pub struct DateTime {
 }
 impl DateTime {
     pub fn new(epoch: u32) -> DateTime {
-        let mut days = epoch / 86400;
+        let days = epoch / 86400;
         let time = epoch % 86400;
-        let day_of_week = DayOfWeek::from_days_since_epoch(days);
-        let mut year = 1970;
-        let mut is_leap_year;
-
-        loop {
-            is_leap_year = is_leap(year);
-            if is_leap_year && days >= 366 {
-                year += 1;
-                days -= 366
-            } else if !is_leap_year && days >= 365 {
-                year += 1;
-                days -= 365;
-            } else {
-                break;
-            }
-        }
-        let mut month = 1;
-        for &nb in days_in_month(is_leap_year).iter() {
-            if days < nb {
-                break;
-            }
-            days -= nb;
-            month += 1;
-        }
+        let (year, month, day) = civil_from_days(days);
         DateTime {
             year,
             month,
-            day: (days + 1) as u8,
+            day,
             hour: (time / 60 / 60) as u8,
             min: (time / 60 % 60) as u8,
             sec: (time % 60) as u8,
-            day_of_week,
+            day_of_week: DayOfWeek::from_days_since_epoch(days),
         }
     }
-    pub fn to_epoch(&self) -> Option<u32> {
-        if self.year < 1970 || self.month == 0 || self.month > 12 || self.day == 0 {
-            return None;
+    /// a validated date and time with the day of the week derived from it
+    pub fn from_ymd_hms(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        min: u8,
+        sec: u8,
+    ) -> Result<DateTime, DateTimeError> {
+        let date = DateTime {
+            year,
+            month,
+            day,
+            hour,
+            min,
+            sec,
+            day_of_week: DayOfWeek::Monday,
+        };
+        let epoch = date.to_epoch()?;
+        Ok(DateTime {
+            day_of_week: DayOfWeek::from_days_since_epoch(epoch / 86400),
+            ..date
+        })
+    }
+    /// check every field, the day of the week is not checked
+    pub fn validate(&self) -> Result<(), DateTimeError> {
+        if self.year < 1970 {
+            Err(DateTimeError::Year(self.year))
+        } else if self.month == 0 || self.month > 12 {
+            Err(DateTimeError::Month(self.month))
+        } else if self.day == 0 || self.day > days_of_month(self.year, self.month) {
+            Err(DateTimeError::Day(self.day))
+        } else if self.hour > 23 {
+            Err(DateTimeError::Hour(self.hour))
+        } else if self.min > 59 {
+            Err(DateTimeError::Minute(self.min))
+        } else if self.sec > 59 {
+            Err(DateTimeError::Second(self.sec))
+        } else {
+            Ok(())
         }
-        let mut days = u32::from(self.day) - 1;
-        for y in 1970..self.year {
-            if is_leap(y) {
-                days = days.checked_add(366)?;
-            } else {
-                days = days.checked_add(365)?;
+    }
+    /// the seconds since 1970-01-01 00:00:00, the day of the week is not used
+    pub fn to_epoch(&self) -> Result<u32, DateTimeError> {
+        self.validate()?;
+        let days = days_from_civil(self.year, self.month, self.day);
+        let time = u32::from(self.hour) * 3600 + u32::from(self.min) * 60 + u32::from(self.sec);
+        days.checked_mul(86400)
+            .and_then(|seconds| seconds.checked_add(time))
+            .ok_or(DateTimeError::OutOfRange)
+    }
+
+    //-------------------------------------------------------------------------
+    //                        arithmetic (negative values go back)
+    //-------------------------------------------------------------------------
+    pub fn add_seconds(&self, seconds: i64) -> Result<DateTime, DateTimeError> {
+        let epoch = i64::from(self.to_epoch()?) + seconds;
+        u32::try_from(epoch)
+            .map(DateTime::new)
+            .map_err(|_| DateTimeError::OutOfRange)
+    }
+    pub fn add_minutes(&self, minutes: i64) -> Result<DateTime, DateTimeError> {
+        self.add_seconds(minutes.saturating_mul(60))
+    }
+    #[cfg(test)]
+    pub fn add_hours(&self, hours: i64) -> Result<DateTime, DateTimeError> {
+        self.add_seconds(hours.saturating_mul(3600))
+    }
+    pub fn add_days(&self, days: i64) -> Result<DateTime, DateTimeError> {
+        self.add_seconds(days.saturating_mul(86400))
+    }
+    /// the same day and time `months` later, the day is clamped to the end of a shorter month
+    /// (e.g one month after January 31 is the last day of February)
+    #[cfg(test)]
+    pub fn add_months(&self, months: i32) -> Result<DateTime, DateTimeError> {
+        self.validate()?;
+        let total = i64::from(self.year) * 12 + i64::from(self.month) - 1 + i64::from(months);
+        let year = u16::try_from(total.div_euclid(12)).map_err(|_| DateTimeError::OutOfRange)?;
+        let month = total.rem_euclid(12) as u8 + 1;
+        if year < 1970 {
+            return Err(DateTimeError::OutOfRange);
+        }
+        let day = self.day.min(days_of_month(year, month));
+        DateTime::from_ymd_hms(year, month, day, self.hour, self.min, self.sec)
+    }
+    /// the seconds from `earlier` to `self`, negative if `earlier` is after
+    #[cfg(test)]
+    pub fn seconds_since(&self, earlier: &DateTime) -> Result<i64, DateTimeError> {
+        Ok(i64::from(self.to_epoch()?) - i64::from(earlier.to_epoch()?))
+    }
+    /// the first `hour:min:00` strictly after `self` in one of the days of `days`, an empty
+    /// mask is any day
+    pub fn next_occurrence(
+        &self,
+        hour: u8,
+        min: u8,
+        days: DayMask,
+    ) -> Result<DateTime, DateTimeError> {
+        let days = if days.is_empty() {
+            DayMask::EVERY_DAY
+        } else {
+            days
+        };
+        let today = DateTime::from_ymd_hms(self.year, self.month, self.day, hour, min, 0)?;
+        // today could be already past and then the same day of the
+        // next week, so eight days
+        for offset in 0..8 {
+            let candidate = today.add_days(offset)?;
+            if candidate > *self && days.contains(candidate.day_of_week) {
+                return Ok(candidate);
             }
         }
+        unreachable!("every week has all the days")
+    }
+}
+/// the time and the date in two lines, see `DateTimeFormat` for others
+impl ::core::fmt::Display for DateTime {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
+        DateTimeFormat::DISPLAY.write(f, self)
+    }
+}
 
-        for &m in days_in_month(is_leap(self.year))
-            .iter()
-            .take(self.month as usize - 1)
-        {
-            days = days.checked_add(m)?;
+//-------------------------------------------------------------------------
+//                        ISO 8601
+//-------------------------------------------------------------------------
+const ISO_DATE_LEN: usize = "YYYY-MM-DD".len();
+const ISO_TIME_LEN: usize = "HH:MM:SS".len();
+const ISO_LEN: usize = ISO_DATE_LEN + 1 + ISO_TIME_LEN;
+
+/// Which parts of a `DateTime` are in an ISO 8601 text
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IsoForm {
+    /// `YYYY-MM-DDTHH:MM:SS`
+    DateTime,
+    /// `YYYY-MM-DD`
+    Date,
+    /// `HH:MM:SS`
+    Time,
+}
+
+/// Why an ISO 8601 text is not a valid `DateTime`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// not the length of any `IsoForm`
+    Length(usize),
+    /// there is no `-`, `T` or `:` at this position
+    Separator(usize),
+    /// the year is not four digits
+    Year,
+    /// the month is not two digits
+    Month,
+    /// the day is not two digits
+    Day,
+    /// the hour is not two digits
+    Hour,
+    /// the minute is not two digits
+    Minute,
+    /// the second is not two digits
+    Second,
+    /// the numbers are not a valid date and time
+    Invalid(DateTimeError),
+}
+impl From<DateTimeError> for ParseError {
+    fn from(error: DateTimeError) -> Self {
+        ParseError::Invalid(error)
+    }
+}
+impl ::core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
+        match self {
+            ParseError::Length(len) => write!(f, "unexpected length {}", len),
+            ParseError::Separator(index) => write!(f, "missing separator at {}", index),
+            ParseError::Year => write!(f, "the year is not YYYY"),
+            ParseError::Month => write!(f, "the month is not MM"),
+            ParseError::Day => write!(f, "the day is not DD"),
+            ParseError::Hour => write!(f, "the hour is not HH"),
+            ParseError::Minute => write!(f, "the minute is not MM"),
+            ParseError::Second => write!(f, "the second is not SS"),
+            ParseError::Invalid(error) => write!(f, "{}", error),
         }
+    }
+}
 
-        let time = u32::from(self.hour) * 3600 + u32::from(self.min) * 60 + u32::from(self.sec);
-        let epoch = days.checked_mul(86400)?.checked_add(time)?;
-        Some(epoch)
+/// the number in `text[start..start + len]` if they are all ASCII digits
+fn digits(text: &[u8], start: usize, len: usize) -> Option<u16> {
+    text[start..start + len].iter().try_fold(0, |value, &byte| {
+        (byte as char)
+            .to_digit(10)
+            .map(|digit| value * 10 + digit as u16)
+    })
+}
+
+fn separator(text: &[u8], index: usize, expected: u8) -> Result<(), ParseError> {
+    if text[index] == expected {
+        Ok(())
+    } else {
+        Err(ParseError::Separator(index))
     }
 }
-impl ::core::fmt::Display for DateTime {
+
+fn parse_iso_date(text: &[u8]) -> Result<(u16, u8, u8), ParseError> {
+    let year = digits(text, 0, 4).ok_or(ParseError::Year)?;
+    separator(text, 4, b'-')?;
+    let month = digits(text, 5, 2).ok_or(ParseError::Month)? as u8;
+    separator(text, 7, b'-')?;
+    let day = digits(text, 8, 2).ok_or(ParseError::Day)? as u8;
+    Ok((year, month, day))
+}
+
+/// the time starts at `start` to have the positions of the separators in the whole text
+fn parse_iso_time(text: &[u8], start: usize) -> Result<(u8, u8, u8), ParseError> {
+    let hour = digits(text, start, 2).ok_or(ParseError::Hour)? as u8;
+    separator(text, start + 2, b':')?;
+    let min = digits(text, start + 3, 2).ok_or(ParseError::Minute)? as u8;
+    separator(text, start + 5, b':')?;
+    let sec = digits(text, start + 6, 2).ok_or(ParseError::Second)? as u8;
+    Ok((hour, min, sec))
+}
+
+impl DateTime {
+    /// parse any `IsoForm`, a missing date is 1970-01-01 and a missing time is midnight
+    #[cfg(test)]
+    pub fn from_iso(text: &str) -> Result<DateTime, ParseError> {
+        DateTime::new(0).with_iso(text)
+    }
+    /// parse any `IsoForm` and take the missing date or time from `self`, e.g to change only
+    /// the time of today
+    pub fn with_iso(&self, text: &str) -> Result<DateTime, ParseError> {
+        let bytes = text.as_bytes();
+        let ((year, month, day), (hour, min, sec)) = match bytes.len() {
+            ISO_LEN => {
+                separator(bytes, ISO_DATE_LEN, b'T')?;
+                (
+                    parse_iso_date(bytes)?,
+                    parse_iso_time(bytes, ISO_DATE_LEN + 1)?,
+                )
+            }
+            ISO_DATE_LEN => (parse_iso_date(bytes)?, (0, 0, 0)),
+            ISO_TIME_LEN => ((self.year, self.month, self.day), parse_iso_time(bytes, 0)?),
+            len => return Err(ParseError::Length(len)),
+        };
+        Ok(DateTime::from_ymd_hms(year, month, day, hour, min, sec)?)
+    }
+    /// something to use with `write!` and `{}` that writes the `form` of ISO 8601
+    pub fn iso(&self, form: IsoForm) -> Iso<'_> {
+        Iso { date: self, form }
+    }
+}
+
+pub struct Iso<'a> {
+    date: &'a DateTime,
+    form: IsoForm,
+}
+impl ::core::fmt::Display for Iso<'_> {
     fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
-        write!(
-            f,
-            "{:02}:{:02}:{:02}\n({})",
-            self.hour, self.min, self.sec, self.day_of_week,
-        )
+        let date = self.date;
+        if self.form != IsoForm::Time {
+            write!(f, "{:04}-{:02}-{:02}", date.year, date.month, date.day)?;
+        }
+        if self.form == IsoForm::DateTime {
+            f.write_str("T")?;
+        }
+        if self.form != IsoForm::Date {
+            write!(f, "{:02}:{:02}:{:02}", date.hour, date.min, date.sec)?;
+        }
+        Ok(())
+    }
+}
+
+/// the epoch of a date and time, validated only once unlike `from_ymd_hms` and `to_epoch`
+pub fn epoch_from_ymd_hms(
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    min: u8,
+    sec: u8,
+) -> Result<u32, DateTimeError> {
+    DateTime {
+        year,
+        month,
+        day,
+        hour,
+        min,
+        sec,
+        // `to_epoch` does not use it
+        day_of_week: DayOfWeek::Monday,
+    }
+    .to_epoch()
+}
+
+/// The dates shared by the tests of the modules
+#[cfg(test)]
+pub mod fixture {
+    /// 2023-04-11 22:18:00 UTC, a Tuesday
+    pub const NOW: u32 = 1681251480;
+
+    /// the epoch of a valid date at the second zero
+    pub fn epoch(year: u16, month: u8, day: u8, hour: u8, min: u8) -> u32 {
+        super::epoch_from_ymd_hms(year, month, day, hour, min, 0).unwrap()
     }
 }
 
@@ -167,6 +530,7 @@ mod test {
     };
 
     #[test]
+    #[allow(clippy::legacy_numeric_constants)]
     fn test_epoch() {
         assert_eq!(DateTime::new(0), EPOCH);
         assert_eq!(DateTime::new(core::u32::MAX), END_OF_TIME);
@@ -182,15 +546,15 @@ mod test {
                 day_of_week: DayOfWeek::Saturday,
             }
         );
-        assert_eq!(EPOCH.to_epoch(), Some(0));
-        assert_eq!(END_OF_TIME.to_epoch(), Some(core::u32::MAX));
+        assert_eq!(EPOCH.to_epoch(), Ok(0));
+        assert_eq!(END_OF_TIME.to_epoch(), Ok(core::u32::MAX));
         assert_eq!(
             DateTime {
                 sec: 16,
                 ..END_OF_TIME
             }
             .to_epoch(),
-            None
+            Err(DateTimeError::OutOfRange)
         );
         assert_eq!(
             DateTime {
@@ -203,7 +567,373 @@ mod test {
                 day_of_week: DayOfWeek::Wednesday,
             }
             .to_epoch(),
-            None
+            Err(DateTimeError::Year(1969))
+        );
+    }
+
+    #[test]
+    fn test_from_ymd_hms() {
+        // the first boot time was a Saturday by hand, and it is Tuesday
+        let date = DateTime::from_ymd_hms(2023, 4, 11, 0, 0, 0).unwrap();
+        assert_eq!(date.day_of_week, DayOfWeek::Tuesday);
+        assert_eq!(date.to_epoch(), Ok(1681171200));
+        assert_eq!(DateTime::from_ymd_hms(1970, 1, 1, 0, 0, 0), Ok(EPOCH));
+        assert_eq!(
+            DateTime::from_ymd_hms(2106, 2, 7, 6, 28, 15),
+            Ok(END_OF_TIME)
+        );
+        assert_eq!(
+            DateTime::from_ymd_hms(2024, 2, 29, 23, 59, 59).map(|date| date.day_of_week),
+            Ok(DayOfWeek::Thursday)
+        );
+    }
+
+    #[test]
+    fn test_from_ymd_hms_errors() {
+        use DateTimeError::*;
+        assert_eq!(
+            DateTime::from_ymd_hms(1969, 12, 31, 0, 0, 0),
+            Err(Year(1969))
+        );
+        assert_eq!(DateTime::from_ymd_hms(2023, 0, 1, 0, 0, 0), Err(Month(0)));
+        assert_eq!(DateTime::from_ymd_hms(2023, 13, 1, 0, 0, 0), Err(Month(13)));
+        assert_eq!(DateTime::from_ymd_hms(2023, 4, 0, 0, 0, 0), Err(Day(0)));
+        assert_eq!(DateTime::from_ymd_hms(2023, 4, 31, 0, 0, 0), Err(Day(31)));
+        assert_eq!(DateTime::from_ymd_hms(2023, 2, 29, 0, 0, 0), Err(Day(29)));
+        assert_eq!(DateTime::from_ymd_hms(2100, 2, 29, 0, 0, 0), Err(Day(29)));
+        assert!(DateTime::from_ymd_hms(2000, 2, 29, 0, 0, 0).is_ok());
+        assert_eq!(DateTime::from_ymd_hms(2023, 4, 11, 24, 0, 0), Err(Hour(24)));
+        assert_eq!(
+            DateTime::from_ymd_hms(2023, 4, 11, 0, 60, 0),
+            Err(Minute(60))
+        );
+        assert_eq!(
+            DateTime::from_ymd_hms(2023, 4, 11, 0, 0, 60),
+            Err(Second(60))
+        );
+        assert_eq!(DateTime::from_ymd_hms(2107, 1, 1, 0, 0, 0), Err(OutOfRange));
+    }
+
+    /// the previous implementation of `DateTime::new`, one year at a time
+    fn legacy_new(epoch: u32) -> DateTime {
+        let mut days = epoch / 86400;
+        let time = epoch % 86400;
+        let day_of_week = DayOfWeek::from_days_since_epoch(days);
+        let mut year = 1970;
+        let mut is_leap_year;
+
+        loop {
+            is_leap_year = is_leap(year);
+            if is_leap_year && days >= 366 {
+                year += 1;
+                days -= 366
+            } else if !is_leap_year && days >= 365 {
+                year += 1;
+                days -= 365;
+            } else {
+                break;
+            }
+        }
+        let mut month = 1;
+        for &nb in days_in_month(is_leap_year).iter() {
+            if days < nb {
+                break;
+            }
+            days -= nb;
+            month += 1;
+        }
+        DateTime {
+            year,
+            month,
+            day: (days + 1) as u8,
+            hour: (time / 60 / 60) as u8,
+            min: (time / 60 % 60) as u8,
+            sec: (time % 60) as u8,
+            day_of_week,
+        }
+    }
+
+    /// the previous implementation of `DateTime::to_epoch`, for valid dates
+    fn legacy_to_epoch(date: &DateTime) -> Option<u32> {
+        let mut days = u32::from(date.day) - 1;
+        for y in 1970..date.year {
+            if is_leap(y) {
+                days = days.checked_add(366)?;
+            } else {
+                days = days.checked_add(365)?;
+            }
+        }
+        for &m in days_in_month(is_leap(date.year))
+            .iter()
+            .take(date.month as usize - 1)
+        {
+            days = days.checked_add(m)?;
+        }
+        let time = u32::from(date.hour) * 3600 + u32::from(date.min) * 60 + u32::from(date.sec);
+        days.checked_mul(86400)?.checked_add(time)
+    }
+
+    #[test]
+    fn test_same_as_legacy_every_day() {
+        // every day of the u32 range, at its first and last second
+        for days in 0..=u32::MAX / 86400 {
+            for &epoch in [days * 86400, (days * 86400).saturating_add(86399)].iter() {
+                let date = DateTime::new(epoch);
+                assert_eq!(date, legacy_new(epoch), "epoch {}", epoch);
+                assert_eq!(date.to_epoch().ok(), legacy_to_epoch(&date));
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_as_legacy_random_seconds() {
+        // a simple xorshift, the same sequence every run
+        let mut state: u32 = 0x2023_0428;
+        for _ in 0..1_000_000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let date = DateTime::new(state);
+            assert_eq!(date, legacy_new(state), "epoch {}", state);
+            assert_eq!(date.to_epoch(), Ok(state));
+        }
+    }
+
+    #[test]
+    fn test_to_epoch_after_the_range() {
+        for year in [2106, 2107, 2400, u16::MAX].iter() {
+            let date = DateTime {
+                year: *year,
+                month: 12,
+                day: 31,
+                ..EPOCH
+            };
+            assert_eq!(date.to_epoch().ok(), legacy_to_epoch(&date));
+            assert_eq!(date.to_epoch(), Err(DateTimeError::OutOfRange));
+        }
+    }
+
+    #[test]
+    fn test_round_trip_every_day() {
+        let mut epoch = 0u32;
+        while let Some(next) = epoch.checked_add(86400 + 3661) {
+            let date = DateTime::new(epoch);
+            let built = DateTime::from_ymd_hms(
+                date.year, date.month, date.day, date.hour, date.min, date.sec,
+            );
+            assert_eq!(built, Ok(date));
+            assert_eq!(built.and_then(|date| date.to_epoch()), Ok(epoch));
+            epoch = next;
+        }
+    }
+
+    #[test]
+    fn test_days_of_month() {
+        assert_eq!(days_of_month(2023, 2), 28);
+        assert_eq!(days_of_month(2024, 2), 29);
+        assert_eq!(days_of_month(2100, 2), 28);
+        assert_eq!(days_of_month(2000, 2), 29);
+        assert_eq!(days_of_month(2023, 4), 30);
+        assert_eq!(days_of_month(2023, 12), 31);
+    }
+
+    #[test]
+    fn test_day_mask() {
+        use DayOfWeek::*;
+        let mask = DayMask::NONE.with(Monday).with(Sunday);
+        assert!(mask.contains(Monday) && mask.contains(Sunday));
+        assert!(!mask.contains(Tuesday));
+        assert_eq!(mask.without(Sunday), DayMask::NONE.with(Monday));
+        assert_eq!(DayMask::from_bits(0xff), DayMask::EVERY_DAY);
+        assert!(DayMask::NONE.is_empty());
+        let mut text = std::string::String::new();
+        use core::fmt::Write;
+        write!(&mut text, "{}", DayMask::WEEKDAYS).unwrap();
+        assert_eq!(text, "MTWTF--");
+        text.clear();
+        DayMask::WEEKEND
+            .with(Wednesday)
+            .write(&mut text, &crate::locale::SPANISH)
+            .unwrap();
+        assert_eq!(text, "--X--SD");
+    }
+
+    fn date(year: u16, month: u8, day: u8, hour: u8, min: u8, sec: u8) -> DateTime {
+        DateTime::from_ymd_hms(year, month, day, hour, min, sec).unwrap()
+    }
+
+    #[test]
+    fn test_add_and_sub() {
+        let now = date(2023, 4, 30, 23, 55, 30);
+        assert_eq!(now.add_minutes(9), Ok(date(2023, 5, 1, 0, 4, 30)));
+        assert_eq!(now.add_seconds(-30), Ok(date(2023, 4, 30, 23, 55, 0)));
+        assert_eq!(now.add_hours(-24), Ok(date(2023, 4, 29, 23, 55, 30)));
+        assert_eq!(now.add_days(246), Ok(date(2024, 1, 1, 23, 55, 30)));
+        assert_eq!(
+            date(2024, 3, 1, 0, 0, 0).add_days(-1),
+            Ok(date(2024, 2, 29, 0, 0, 0))
+        );
+        // out of the epoch range
+        assert_eq!(EPOCH.add_seconds(-1), Err(DateTimeError::OutOfRange));
+        assert_eq!(END_OF_TIME.add_seconds(1), Err(DateTimeError::OutOfRange));
+        assert_eq!(EPOCH.add_days(i64::MAX), Err(DateTimeError::OutOfRange));
+        // an invalid date is an error and not a wrong answer
+        let invalid = DateTime { day: 31, ..now };
+        assert_eq!(invalid.add_seconds(0), Err(DateTimeError::Day(31)));
+    }
+
+    #[test]
+    fn test_add_months_clamps_the_day() {
+        let end_of_january = date(2023, 1, 31, 7, 0, 0);
+        assert_eq!(end_of_january.add_months(1), Ok(date(2023, 2, 28, 7, 0, 0)));
+        assert_eq!(
+            end_of_january.add_months(13),
+            Ok(date(2024, 2, 29, 7, 0, 0))
+        );
+        assert_eq!(end_of_january.add_months(3), Ok(date(2023, 4, 30, 7, 0, 0)));
+        assert_eq!(
+            end_of_january.add_months(-1),
+            Ok(date(2022, 12, 31, 7, 0, 0))
+        );
+        assert_eq!(
+            end_of_january.add_months(-25),
+            Ok(date(2020, 12, 31, 7, 0, 0))
+        );
+        assert_eq!(
+            end_of_january.add_months(12 * 100),
+            Err(DateTimeError::OutOfRange)
+        );
+        assert_eq!(EPOCH.add_months(-1), Err(DateTimeError::OutOfRange));
+        // the day of the week is derived again
+        assert_eq!(
+            end_of_january.add_months(1).map(|date| date.day_of_week),
+            Ok(DayOfWeek::Tuesday)
+        );
+    }
+
+    #[test]
+    fn test_seconds_since() {
+        let now = date(2023, 4, 11, 22, 18, 0);
+        let tomorrow = now.next_occurrence(7, 0, DayMask::NONE).unwrap();
+        assert_eq!(tomorrow, date(2023, 4, 12, 7, 0, 0));
+        assert_eq!(tomorrow.seconds_since(&now), Ok(8 * 3600 + 42 * 60));
+        assert_eq!(now.seconds_since(&tomorrow), Ok(-(8 * 3600 + 42 * 60)));
+        assert_eq!(END_OF_TIME.seconds_since(&EPOCH), Ok(i64::from(u32::MAX)));
+    }
+
+    fn iso<const N: usize>(date: &DateTime, form: IsoForm) -> heapless::String<N> {
+        use core::fmt::Write;
+        let mut text = heapless::String::new();
+        write!(&mut text, "{}", date.iso(form)).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_iso_forms() {
+        let now = date(2023, 4, 11, 22, 18, 5);
+        assert_eq!(iso::<32>(&now, IsoForm::DateTime), "2023-04-11T22:18:05");
+        assert_eq!(iso::<32>(&now, IsoForm::Date), "2023-04-11");
+        assert_eq!(iso::<32>(&now, IsoForm::Time), "22:18:05");
+        assert_eq!(DateTime::from_iso("2023-04-11T22:18:05"), Ok(now.clone()));
+        assert_eq!(
+            DateTime::from_iso("2023-04-11"),
+            Ok(date(2023, 4, 11, 0, 0, 0))
+        );
+        assert_eq!(
+            DateTime::from_iso("22:18:05"),
+            Ok(date(1970, 1, 1, 22, 18, 5))
+        );
+        // only the time changes
+        assert_eq!(now.with_iso("07:30:00"), Ok(date(2023, 4, 11, 7, 30, 0)));
+        assert_eq!(now.with_iso("2024-02-29"), Ok(date(2024, 2, 29, 0, 0, 0)));
+    }
+
+    #[test]
+    fn test_iso_errors() {
+        let cases = [
+            ("", ParseError::Length(0)),
+            ("2023-04-11T22:18", ParseError::Length(16)),
+            ("2023-04-11 22:18:05", ParseError::Separator(10)),
+            ("2023/04/11", ParseError::Separator(4)),
+            ("22-18:05", ParseError::Separator(2)),
+            ("2023-04-11T22:18-05", ParseError::Separator(16)),
+            ("20x3-04-11", ParseError::Year),
+            ("2023-+4-11", ParseError::Month),
+            ("2023-04-1a", ParseError::Day),
+            ("2023-04-11T 2:18:05", ParseError::Hour),
+            ("22:1 :05", ParseError::Minute),
+            ("22:18:0é", ParseError::Length(9)),
+            ("22:18:-5", ParseError::Second),
+            ("1969-12-31", ParseError::Invalid(DateTimeError::Year(1969))),
+            ("2023-13-01", ParseError::Invalid(DateTimeError::Month(13))),
+            ("2023-02-29", ParseError::Invalid(DateTimeError::Day(29))),
+            ("24:00:00", ParseError::Invalid(DateTimeError::Hour(24))),
+            ("23:60:00", ParseError::Invalid(DateTimeError::Minute(60))),
+            ("23:59:60", ParseError::Invalid(DateTimeError::Second(60))),
+            (
+                "2106-02-07T06:28:16",
+                ParseError::Invalid(DateTimeError::OutOfRange),
+            ),
+        ];
+        for &(text, error) in cases.iter() {
+            assert_eq!(DateTime::from_iso(text), Err(error), "{}", text);
+        }
+        assert!(DateTime::from_iso("2106-02-07T06:28:15").is_ok());
+    }
+
+    #[test]
+    fn test_iso_round_trip_in_the_whole_range() {
+        // every day at a different time and the limits of the range
+        let seconds = (0..=u32::MAX / 86400)
+            .map(|days| (days * 86400).saturating_add(days % 86400))
+            .chain([0, 86399, u32::MAX].iter().copied());
+        for epoch in seconds {
+            let date = DateTime::new(epoch);
+            let text = iso::<19>(&date, IsoForm::DateTime);
+            assert_eq!(DateTime::from_iso(&text), Ok(date.clone()), "{}", text);
+            let day = iso::<10>(&date, IsoForm::Date);
+            let time = iso::<8>(&date, IsoForm::Time);
+            let back = DateTime::from_iso(&day).and_then(|day| day.with_iso(&time));
+            assert_eq!(back, Ok(date));
+        }
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        // 2023-04-11 was a Tuesday
+        let now = date(2023, 4, 11, 22, 18, 0);
+        assert_eq!(
+            now.next_occurrence(23, 0, DayMask::NONE),
+            Ok(date(2023, 4, 11, 23, 0, 0))
+        );
+        // strictly after
+        assert_eq!(
+            now.next_occurrence(22, 18, DayMask::NONE),
+            Ok(date(2023, 4, 12, 22, 18, 0))
+        );
+        assert_eq!(
+            now.next_occurrence(9, 0, DayMask::WEEKEND),
+            Ok(date(2023, 4, 15, 9, 0, 0))
+        );
+        // the same day of the next week
+        let tuesday = DayMask::NONE.with(DayOfWeek::Tuesday);
+        assert_eq!(
+            now.next_occurrence(6, 0, tuesday),
+            Ok(date(2023, 4, 18, 6, 0, 0))
+        );
+        // the end of the year
+        let monday = DayMask::NONE.with(DayOfWeek::Monday);
+        assert_eq!(
+            date(2023, 12, 26, 0, 0, 0).next_occurrence(6, 0, monday),
+            Ok(date(2024, 1, 1, 6, 0, 0))
+        );
+        assert_eq!(
+            now.next_occurrence(24, 0, DayMask::NONE),
+            Err(DateTimeError::Hour(24))
+        );
+        assert_eq!(
+            END_OF_TIME.next_occurrence(6, 0, DayMask::NONE),
+            Err(DateTimeError::OutOfRange)
         );
     }
 }
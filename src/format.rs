@@ -0,0 +1,276 @@
+/// Formatting of the date and time for the display and the logs, without allocations
+use crate::datetime::DateTime;
+use crate::locale::Language;
+use core::fmt::{self, Write};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Hours {
+    /// 00:00 to 23:59
+    H24,
+    /// 12:00 AM to 11:59 PM
+    H12,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DateOrder {
+    Hidden,
+    /// 11/04/2023
+    Dmy,
+    /// 04/11/2023
+    Mdy,
+    /// 2023-04-11
+    Iso,
+    /// 11 April 2023
+    LongMonth,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Weekday {
+    Hidden,
+    /// Tue
+    Short,
+    /// Tuesday
+    Long,
+}
+
+/// How a `DateTime` is written: the time, the `separator` and then the weekday and the date
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DateTimeFormat {
+    pub hours: Hours,
+    pub seconds: bool,
+    pub date: DateOrder,
+    pub weekday: Weekday,
+    pub separator: &'static str,
+    /// of the names of the days and the months
+    pub language: Language,
+}
+
+impl DateTimeFormat {
+    /// two lines that fit in the OLED display, the date order is the one of the language
+    pub const DISPLAY: DateTimeFormat = DateTimeFormat {
+        hours: Hours::H24,
+        seconds: true,
+        date: DateOrder::Mdy,
+        weekday: Weekday::Short,
+        separator: "\n",
+        language: Language::English,
+    };
+    /// one line for the UART logs
+    pub const LOG: DateTimeFormat = DateTimeFormat {
+        hours: Hours::H24,
+        seconds: true,
+        date: DateOrder::Iso,
+        weekday: Weekday::Hidden,
+        separator: " ",
+        language: Language::English,
+    };
+    /// the answer of `time get` in the console
+    pub const CONSOLE: DateTimeFormat = DateTimeFormat {
+        hours: Hours::H24,
+        seconds: true,
+        date: DateOrder::LongMonth,
+        weekday: Weekday::Long,
+        separator: " ",
+        language: Language::English,
+    };
+
+    /// the `DISPLAY` format in `language`
+    pub fn display_in(language: Language) -> DateTimeFormat {
+        DateTimeFormat {
+            date: language.locale().date_order,
+            language,
+            ..DateTimeFormat::DISPLAY
+        }
+    }
+
+    pub fn write_time<W: Write>(&self, w: &mut W, date: &DateTime) -> fmt::Result {
+        let hour = match self.hours {
+            Hours::H24 => date.hour,
+            Hours::H12 => match date.hour % 12 {
+                0 => 12,
+                hour => hour,
+            },
+        };
+        write!(w, "{:02}:{:02}", hour, date.min)?;
+        if self.seconds {
+            write!(w, ":{:02}", date.sec)?;
+        }
+        if self.hours == Hours::H12 {
+            w.write_str(if date.hour < 12 { " AM" } else { " PM" })?;
+        }
+        Ok(())
+    }
+
+    /// the weekday and the date, nothing if both are hidden
+    pub fn write_date<W: Write>(&self, w: &mut W, date: &DateTime) -> fmt::Result {
+        let locale = self.language.locale();
+        match self.weekday {
+            Weekday::Hidden => {}
+            Weekday::Short => w.write_str(locale.short_weekday(date.day_of_week))?,
+            Weekday::Long => w.write_str(locale.weekday(date.day_of_week))?,
+        }
+        if self.weekday != Weekday::Hidden && self.date != DateOrder::Hidden {
+            w.write_char(' ')?;
+        }
+        let (year, month, day) = (date.year, date.month, date.day);
+        match self.date {
+            DateOrder::Hidden => Ok(()),
+            DateOrder::Dmy => write!(w, "{:02}/{:02}/{:04}", day, month, year),
+            DateOrder::Mdy => write!(w, "{:02}/{:02}/{:04}", month, day, year),
+            DateOrder::Iso => write!(w, "{:04}-{:02}-{:02}", year, month, day),
+            DateOrder::LongMonth => write!(w, "{} {} {}", day, locale.month(month), year),
+        }
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W, date: &DateTime) -> fmt::Result {
+        self.write_time(w, date)?;
+        if self.date != DateOrder::Hidden || self.weekday != Weekday::Hidden {
+            w.write_str(self.separator)?;
+            self.write_date(w, date)?;
+        }
+        Ok(())
+    }
+
+    /// something to use with `write!` and `{}`
+    pub fn display<'a>(&'a self, date: &'a DateTime) -> Formatted<'a> {
+        Formatted { format: self, date }
+    }
+}
+
+impl Default for DateTimeFormat {
+    fn default() -> Self {
+        Self::DISPLAY
+    }
+}
+
+pub struct Formatted<'a> {
+    format: &'a DateTimeFormat,
+    date: &'a DateTime,
+}
+
+impl fmt::Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.format.write(f, self.date)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use heapless::String;
+
+    fn date(hour: u8, min: u8, sec: u8) -> DateTime {
+        // 2023-04-11 was a Tuesday
+        DateTime::from_ymd_hms(2023, 4, 11, hour, min, sec).unwrap()
+    }
+
+    fn format(format: DateTimeFormat, date: &DateTime) -> String<64> {
+        let mut text = String::new();
+        format.write(&mut text, date).unwrap();
+        text
+    }
+
+    #[test]
+    fn display_and_log_formats() {
+        let now = date(22, 18, 5);
+        assert_eq!(
+            format(DateTimeFormat::DISPLAY, &now),
+            "22:18:05\nTue 04/11/2023"
+        );
+        assert_eq!(
+            format(DateTimeFormat::display_in(Language::Spanish), &now),
+            "22:18:05\nmar 11/04/2023"
+        );
+        assert_eq!(format(DateTimeFormat::LOG, &now), "22:18:05 2023-04-11");
+        assert_eq!(
+            format(DateTimeFormat::CONSOLE, &now),
+            "22:18:05 Tuesday 11 April 2023"
+        );
+        // and the `Display` of `DateTime` is the one of the display
+        let mut text: String<64> = String::new();
+        write!(&mut text, "{}", now).unwrap();
+        assert_eq!(text, "22:18:05\nTue 04/11/2023");
+    }
+
+    #[test]
+    fn twelve_hours() {
+        let h12 = DateTimeFormat {
+            hours: Hours::H12,
+            seconds: false,
+            date: DateOrder::Hidden,
+            weekday: Weekday::Hidden,
+            separator: " ",
+            language: Language::English,
+        };
+        assert_eq!(format(h12, &date(0, 5, 0)), "12:05 AM");
+        assert_eq!(format(h12, &date(7, 30, 0)), "07:30 AM");
+        assert_eq!(format(h12, &date(12, 0, 0)), "12:00 PM");
+        assert_eq!(format(h12, &date(23, 59, 59)), "11:59 PM");
+        let with_seconds = DateTimeFormat {
+            seconds: true,
+            ..h12
+        };
+        assert_eq!(format(with_seconds, &date(13, 1, 2)), "01:01:02 PM");
+    }
+
+    #[test]
+    fn date_orders_and_weekdays() {
+        let now = date(7, 0, 0);
+        let only_date = |date, weekday| DateTimeFormat {
+            date,
+            weekday,
+            ..DateTimeFormat::LOG
+        };
+        let cases = [
+            (DateOrder::Dmy, Weekday::Hidden, "07:00:00 11/04/2023"),
+            (DateOrder::Mdy, Weekday::Hidden, "07:00:00 04/11/2023"),
+            (DateOrder::Iso, Weekday::Long, "07:00:00 Tuesday 2023-04-11"),
+            (DateOrder::Hidden, Weekday::Short, "07:00:00 Tue"),
+            (DateOrder::Hidden, Weekday::Hidden, "07:00:00"),
+        ];
+        for &(order, weekday, expected) in cases.iter() {
+            assert_eq!(format(only_date(order, weekday), &now), expected);
+        }
+    }
+
+    #[test]
+    fn names_in_spanish() {
+        let now = date(7, 0, 0);
+        let spanish = |date, weekday| DateTimeFormat {
+            date,
+            weekday,
+            language: Language::Spanish,
+            ..DateTimeFormat::LOG
+        };
+        let cases = [
+            (DateOrder::Dmy, Weekday::Short, "07:00:00 mar 11/04/2023"),
+            (
+                DateOrder::LongMonth,
+                Weekday::Long,
+                "07:00:00 martes 11 abril 2023",
+            ),
+        ];
+        for &(order, weekday, expected) in cases.iter() {
+            assert_eq!(format(spanish(order, weekday), &now), expected);
+        }
+        let english = DateTimeFormat {
+            date: DateOrder::LongMonth,
+            ..DateTimeFormat::LOG
+        };
+        assert_eq!(format(english, &now), "07:00:00 11 April 2023");
+    }
+
+    #[test]
+    fn small_buffer_is_an_error() {
+        let now = date(7, 0, 0);
+        let mut text: String<18> = String::new();
+        assert!(DateTimeFormat::LOG.write(&mut text, &now).is_err());
+        let mut text: String<19> = String::new();
+        DateTimeFormat::LOG.write(&mut text, &now).unwrap();
+        assert_eq!(text, "07:00:00 2023-04-11");
+        // and with `write!`
+        let mut text: String<64> = String::new();
+        write!(&mut text, "[{}]", DateTimeFormat::LOG.display(&now)).unwrap();
+        assert_eq!(text, "[07:00:00 2023-04-11]");
+    }
+}
@@ -1,47 +1,499 @@
-// use embedded_hal as hal;
+/// Logs with levels, tags and timestamps over any `embedded_hal` serial writer, the UART sends
+/// them from a ring buffer in its interrupt
+use crate::datetime::{DateTime, IsoForm};
+use core::fmt::{self, Write};
+use embedded_hal::serial;
+use heapless::Deque;
+#[cfg(not(test))]
+use stm32f1xx_hal::serial::{Instance, Tx};
 
-// use hal::serial::Write;
-use nb::block;
-use stm32f1xx_hal::pac::USART1;
+/// the importance of a log line, the lines below the level of the `Logger` are not sent
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
 
-use stm32f1xx_hal::serial::Tx;
+impl Level {
+    pub fn label(self) -> &'static str {
+        match self {
+            Level::Debug => "DBG",
+            Level::Info => "INF",
+            Level::Warn => "WRN",
+            Level::Error => "ERR",
+        }
+    }
 
-pub struct Logger {
-    tx_pin: Tx<USART1>,
+    /// the name in lowercase like in the console (e.g `warn`)
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            _ => None,
+        }
+    }
 }
 
-// TODO(elsuizo:2021-11-18): RX ??? maybe???
-/// a UART logger interface
-impl Logger {
-    pub fn new(tx_pin: Tx<USART1>) -> Self {
-        Self { tx_pin }
+/// What a full `RingBuffer` does with the bytes that do not fit
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Overflow {
+    /// the new bytes replace the oldest ones, the last lines are kept
+    DropOldest,
+    /// the new bytes are lost, the first lines are kept
+    DropNewest,
+}
+
+/// The error of a full `RingBuffer` with `Overflow::DropNewest`, the byte was lost
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BufferFull;
+
+/// A queue of bytes with a fixed size that never blocks, it counts the bytes lost
+pub struct RingBuffer<const N: usize> {
+    bytes: Deque<u8, N>,
+    overflow: Overflow,
+    dropped: u32,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new(overflow: Overflow) -> Self {
+        Self {
+            bytes: Deque::new(),
+            overflow,
+            dropped: 0,
+        }
+    }
+
+    /// with `DropOldest` it always fits, the lost bytes are only counted
+    pub fn push(&mut self, byte: u8) -> Result<(), BufferFull> {
+        if self.bytes.is_full() {
+            self.dropped = self.dropped.wrapping_add(1);
+            if self.overflow == Overflow::DropNewest {
+                return Err(BufferFull);
+            }
+            self.bytes.pop_front();
+        }
+        self.bytes.push_back(byte).ok();
+        Ok(())
     }
 
-    pub fn log(&mut self, data: &str) -> Result<(), ()> {
-        self.send("LOG: ".as_bytes())?;
-        self.send(data.as_bytes())?;
-        self.send("\r\n".as_bytes())
+    pub fn front(&self) -> Option<u8> {
+        self.bytes.front().copied()
     }
 
-    pub fn warn(&mut self, data: &str) -> Result<(), ()> {
-        self.send("WRN: ".as_bytes())?;
-        self.send(data.as_bytes())?;
-        self.send("\r\n".as_bytes())
+    pub fn pop(&mut self) -> Option<u8> {
+        self.bytes.pop_front()
     }
 
-    pub fn error(&mut self, data: &str) -> Result<(), ()> {
-        self.send("ERR: ".as_bytes())?;
-        self.send(data.as_bytes())?;
-        self.send("\r\n".as_bytes())
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
     }
 
-    pub fn send(&mut self, buf: &[u8]) -> Result<(), ()> {
-        for &byte in buf {
-            if byte == 0x00 {
-                continue;
+    /// the bytes lost since the last call
+    pub fn take_dropped(&mut self) -> u32 {
+        core::mem::take(&mut self.dropped)
+    }
+}
+
+/// the in-memory writer, it fails like `push`
+impl<const N: usize> serial::Write<u8> for RingBuffer<N> {
+    type Error = BufferFull;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), BufferFull> {
+        self.push(byte).map_err(nb::Error::Other)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), BufferFull> {
+        Ok(())
+    }
+}
+
+/// The TX of a USART with the bytes waiting in a `RingBuffer`, call `drain` in the USART
+/// interrupt
+#[cfg(not(test))]
+pub struct BufferedTx<USART, const N: usize> {
+    tx: Tx<USART>,
+    buffer: RingBuffer<N>,
+}
+
+#[cfg(not(test))]
+impl<USART: Instance, const N: usize> BufferedTx<USART, N> {
+    pub fn new(tx: Tx<USART>, overflow: Overflow) -> Self {
+        Self {
+            tx,
+            buffer: RingBuffer::new(overflow),
+        }
+    }
+
+    /// send the bytes while the TX register is empty, the interrupt is disabled at the end
+    pub fn drain(&mut self) {
+        while let Some(byte) = self.buffer.front() {
+            if self.tx.write(byte).is_err() {
+                // the next byte goes in the next interrupt
+                return;
             }
-            block!(self.tx_pin.write(byte)).ok();
+            self.buffer.pop();
+        }
+        self.tx.unlisten();
+    }
+
+    /// send everything now, blocking, e.g before a reset. The `flush` of `serial::Write` waits
+    /// for the interrupt instead
+    pub fn bflush(&mut self) {
+        while let Some(byte) = self.buffer.pop() {
+            nb::block!(self.tx.write(byte)).ok();
+        }
+        nb::block!(self.tx.flush()).ok();
+        self.tx.unlisten();
+    }
+
+    /// true if all the bytes were sent, the USART stops in the stop mode
+    pub fn is_idle(&self) -> bool {
+        self.buffer.is_empty() && self.tx.is_tx_complete()
+    }
+
+    /// the bytes lost because the buffer was full since the last call
+    pub fn take_dropped(&mut self) -> u32 {
+        self.buffer.take_dropped()
+    }
+}
+
+/// the bytes are queued and it never blocks, a full buffer drops them like its `Overflow`
+#[cfg(not(test))]
+impl<USART: Instance, const N: usize> serial::Write<u8> for BufferedTx<USART, N> {
+    type Error = BufferFull;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), BufferFull> {
+        let pushed = self.buffer.push(byte);
+        // the interrupt arrives as soon as the TX register is empty
+        self.tx.listen();
+        pushed.map_err(nb::Error::Other)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), BufferFull> {
+        if self.is_idle() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+/// Why a log line was not sent completely
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LogError<E> {
+    /// the error of the serial writer
+    Write(E),
+    /// a `Display` of the arguments failed
+    Format,
+}
+
+impl<E: fmt::Debug> fmt::Display for LogError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogError::Write(error) => write!(f, "write error: {:?}", error),
+            LogError::Format => write!(f, "format error"),
+        }
+    }
+}
+
+/// A logger over any serial writer: a USART, a `BufferedTx` or a buffer in memory in the tests
+pub struct Logger<W: serial::Write<u8>> {
+    writer: W,
+    level: Level,
+    timestamps: bool,
+    /// the last UTC epoch from `set_time`
+    now: u32,
+    /// the error of the writer while it was used as a `fmt::Write`
+    error: Option<W::Error>,
+}
+
+impl<W: serial::Write<u8>> Logger<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            level: Level::Info,
+            timestamps: false,
+            now: 0,
+            error: None,
+        }
+    }
+
+    pub fn with_level(self, level: Level) -> Self {
+        Self { level, ..self }
+    }
+
+    /// start every line with the time of the last `set_time`
+    pub fn with_timestamps(self, timestamps: bool) -> Self {
+        Self { timestamps, ..self }
+    }
+
+    pub fn set_level(&mut self, level: Level) {
+        self.level = level;
+    }
+
+    #[cfg(test)]
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    pub fn enabled(&self, level: Level) -> bool {
+        level >= self.level
+    }
+
+    /// the UTC epoch of the timestamps, call it on every RTC second
+    pub fn set_time(&mut self, now: u32) {
+        self.now = now;
+    }
+
+    /// one line like `2023-04-11T22:18:05Z INF alarm: snoozed`, use it with `log!`
+    pub fn write_log(
+        &mut self,
+        level: Level,
+        tag: &str,
+        args: fmt::Arguments,
+    ) -> Result<(), LogError<W::Error>> {
+        if !self.enabled(level) {
+            return Ok(());
+        }
+        let line = self
+            .write_line(level, tag, args)
+            .map_err(|_| self.error.take().map_or(LogError::Format, LogError::Write));
+        // a line cut by an error is also ended, the next one starts in its own line
+        let end = self.send(b"\r\n");
+        line.and(end)
+    }
+
+    fn write_line(&mut self, level: Level, tag: &str, args: fmt::Arguments) -> fmt::Result {
+        if self.timestamps {
+            let now = DateTime::new(self.now);
+            write!(self, "{}Z ", now.iso(IsoForm::DateTime))?;
+        }
+        write!(self, "{} {}: ", level.label(), tag)?;
+        self.write_fmt(args)
+    }
+
+    /// text, the `0x00` bytes are skipped, it blocks if the writer blocks
+    pub fn send(&mut self, buf: &[u8]) -> Result<(), LogError<W::Error>> {
+        for text in buf.split(|&byte| byte == 0x00) {
+            self.send_binary(text)?;
+        }
+        Ok(())
+    }
+
+    /// the bytes verbatim, also the `0x00`, it blocks if the writer blocks
+    pub fn send_binary(&mut self, buf: &[u8]) -> Result<(), LogError<W::Error>> {
+        for &byte in buf {
+            nb::block!(self.writer.write(byte)).map_err(LogError::Write)?;
         }
         Ok(())
     }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+}
+
+impl<W: serial::Write<u8>> fmt::Write for Logger<W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.send(text.as_bytes()).map_err(|error| {
+            // it is returned by `write_log` instead of the `fmt::Error`
+            if let LogError::Write(error) = error {
+                self.error = Some(error);
+            }
+            fmt::Error
+        })
+    }
+}
+
+/// `log!(logger, Level::Info, "tag", "format {}", args)`, the format is done without allocations
+#[macro_export]
+macro_rules! log {
+    ($logger:expr, $level:expr, $tag:expr, $($arg:tt)+) => {
+        $logger.write_log($level, $tag, format_args!($($arg)+))
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use heapless::String;
+    use std::vec::Vec;
+
+    /// keeps everything that is logged, it fails when it is full
+    #[derive(Default)]
+    struct Capture(String<256>);
+
+    #[derive(Debug, PartialEq)]
+    struct Full;
+
+    impl serial::Write<u8> for Capture {
+        type Error = Full;
+
+        fn write(&mut self, byte: u8) -> nb::Result<(), Full> {
+            self.0
+                .push(char::from(byte))
+                .map_err(|_| nb::Error::Other(Full))
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Full> {
+            Ok(())
+        }
+    }
+
+    fn logger() -> Logger<Capture> {
+        Logger::new(Capture::default())
+    }
+
+    #[test]
+    fn format_and_tags() {
+        let mut logger = logger();
+        log!(logger, Level::Info, "ui", "button {} pressed", "Up").unwrap();
+        log!(logger, Level::Error, "alarm", "{} + {} = {}", 1, 2, 1 + 2).unwrap();
+        assert_eq!(
+            logger.writer().0,
+            "INF ui: button Up pressed\r\nERR alarm: 1 + 2 = 3\r\n"
+        );
+    }
+
+    #[test]
+    fn lines_below_the_level_are_not_sent() {
+        let mut logger = logger().with_level(Level::Warn);
+        log!(logger, Level::Debug, "ui", "debug").unwrap();
+        log!(logger, Level::Info, "ui", "info").unwrap();
+        log!(logger, Level::Warn, "ui", "warn").unwrap();
+        logger.set_level(Level::Debug);
+        log!(logger, Level::Debug, "ui", "debug").unwrap();
+        assert_eq!(logger.writer().0, "WRN ui: warn\r\nDBG ui: debug\r\n");
+        assert!(logger.enabled(Level::Error));
+        logger.set_level(Level::Error);
+        assert!(!logger.enabled(Level::Warn));
+        assert_eq!(logger.level(), Level::Error);
+    }
+
+    #[test]
+    fn timestamps() {
+        let mut logger = logger().with_timestamps(true);
+        // 2023-04-11 22:18:00
+        logger.set_time(1681251480);
+        log!(logger, Level::Info, "rtc", "tick").unwrap();
+        assert_eq!(logger.writer().0, "2023-04-11T22:18:00Z INF rtc: tick\r\n");
+    }
+
+    #[test]
+    fn the_error_of_the_writer_is_returned() {
+        let mut logger = logger();
+        let long = [b'a'; 300];
+        let long = core::str::from_utf8(&long).unwrap();
+        assert_eq!(
+            log!(logger, Level::Info, "ui", "{}", long),
+            Err(LogError::Write(Full))
+        );
+        assert_eq!(logger.send(b"more"), Err(LogError::Write(Full)));
+    }
+
+    /// a `Display` that always fails
+    struct Broken;
+
+    impl fmt::Display for Broken {
+        fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+            Err(fmt::Error)
+        }
+    }
+
+    #[test]
+    fn format_errors() {
+        let mut logger = logger();
+        assert_eq!(
+            log!(logger, Level::Info, "ui", "{}", Broken),
+            Err(LogError::Format)
+        );
+        log!(logger, Level::Info, "ui", "next").unwrap();
+        // the line is cut there and ended
+        assert_eq!(logger.writer().0, "INF ui: \r\nINF ui: next\r\n");
+    }
+
+    #[test]
+    fn binary_data_is_sent_verbatim() {
+        let mut logger = logger();
+        logger.send(&[b'a', 0x00, b'b', 0x00]).unwrap();
+        logger.send_binary(&[0x00, 0x7f, b'\n', 0x1b]).unwrap();
+        assert_eq!(
+            logger.writer().0.as_bytes(),
+            [b'a', b'b', 0x00, 0x7f, b'\n', 0x1b]
+        );
+    }
+
+    fn push<const N: usize>(buffer: &mut RingBuffer<N>, bytes: &[u8]) -> Result<(), BufferFull> {
+        bytes
+            .iter()
+            .map(|&byte| buffer.push(byte))
+            .fold(Ok(()), Result::and)
+    }
+
+    #[test]
+    fn ring_buffer_drops_the_oldest_bytes() {
+        let mut buffer: RingBuffer<4> = RingBuffer::new(Overflow::DropOldest);
+        push(&mut buffer, b"abc").unwrap();
+        assert_eq!(buffer.dropped, 0);
+        assert_eq!(push(&mut buffer, b"def"), Ok(()));
+        assert_eq!(buffer.dropped, 2);
+        assert_eq!(buffer.front(), Some(b'c'));
+        let mut sent = Vec::new();
+        while let Some(byte) = buffer.pop() {
+            sent.push(byte);
+        }
+        assert_eq!(sent, b"cdef");
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn ring_buffer_drops_the_newest_bytes() {
+        let mut buffer: RingBuffer<4> = RingBuffer::new(Overflow::DropNewest);
+        assert_eq!(push(&mut buffer, b"abcdef"), Err(BufferFull));
+        assert_eq!(buffer.dropped, 2);
+        assert_eq!(buffer.pop(), Some(b'a'));
+        assert_eq!(push(&mut buffer, b"gh"), Err(BufferFull));
+        assert_eq!(buffer.dropped, 3);
+        let sent: Vec<u8> = core::iter::from_fn(|| buffer.pop()).collect();
+        assert_eq!(sent, b"bcdg");
+    }
+
+    #[test]
+    fn logs_in_a_ring_buffer() {
+        let mut logger = Logger::new(RingBuffer::<64>::new(Overflow::DropOldest));
+        log!(logger, Level::Info, "ui", "{}", "x".repeat(70)).unwrap();
+        // the last 64 of the 80 bytes of the line are kept
+        assert_eq!(logger.writer_mut().take_dropped(), 16);
+        assert_eq!(logger.writer_mut().take_dropped(), 0);
+        while logger.writer_mut().pop().is_some() {}
+        write!(logger, "{} {}", 1, 2).unwrap();
+        let sent: Vec<u8> = core::iter::from_fn(|| logger.writer_mut().pop()).collect();
+        assert_eq!(sent, b"1 2");
+    }
+
+    #[test]
+    fn a_full_buffer_that_drops_the_newest_bytes_is_an_error() {
+        let mut logger = Logger::new(RingBuffer::<16>::new(Overflow::DropNewest));
+        assert_eq!(
+            log!(logger, Level::Info, "ui", "a long line"),
+            Err(LogError::Write(BufferFull))
+        );
+        // the byte that did not fit and the `\r` of the end
+        assert_eq!(logger.writer_mut().take_dropped(), 2);
+    }
+
+    #[test]
+    fn level_names() {
+        assert_eq!(Level::from_name("warn"), Some(Level::Warn));
+        assert_eq!(Level::from_name("WARN"), None);
+        assert!(Level::Debug < Level::Error);
+    }
 }
@@ -0,0 +1,218 @@
+/// Names of the days and months and the menu text in the languages of the clock
+use crate::datetime::DayOfWeek;
+use crate::format::DateOrder;
+
+/// The language of the user interface, it can be changed from the settings menu
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Language {
+    pub fn locale(self) -> &'static Locale {
+        match self {
+            Language::English => &ENGLISH,
+            Language::Spanish => &SPANISH,
+        }
+    }
+
+    /// the next one in the settings menu
+    pub fn next(self) -> Self {
+        match self {
+            Language::English => Language::Spanish,
+            Language::Spanish => Language::English,
+        }
+    }
+}
+
+/// Every text that the user sees in one language
+#[derive(Debug)]
+pub struct Locale {
+    /// the name of the language in itself
+    pub name: &'static str,
+    /// starting on Monday like `DayOfWeek`
+    pub weekdays: [&'static str; 7],
+    pub short_weekdays: [&'static str; 7],
+    /// starting on January
+    pub months: [&'static str; 12],
+    /// of the date in the display
+    pub date_order: DateOrder,
+    /// one letter per day of the alarm repetitions, starting on Monday
+    pub day_letters: [char; 7],
+    /// the menu titles
+    pub alarm_title: &'static str,
+    pub ringing_title: &'static str,
+    pub settings_title: &'static str,
+    /// the fields of the time editor: year, month, day, hour and minute
+    pub fields: [&'static str; 5],
+    pub set: &'static str,
+    pub language: &'static str,
+    /// the alarms screens
+    pub alarm: &'static str,
+    pub alarm_off: &'static str,
+    pub alarm_once: &'static str,
+    pub alarm_empty: &'static str,
+    pub no_alarms: &'static str,
+    pub snooze: &'static str,
+}
+
+impl Locale {
+    pub fn weekday(&self, day: DayOfWeek) -> &'static str {
+        self.weekdays[day as usize]
+    }
+
+    pub fn short_weekday(&self, day: DayOfWeek) -> &'static str {
+        self.short_weekdays[day as usize]
+    }
+
+    /// the name of the `month` between 1 and 12, empty if it is out of range
+    pub fn month(&self, month: u8) -> &'static str {
+        self.months
+            .get(usize::from(month).wrapping_sub(1))
+            .copied()
+            .unwrap_or("")
+    }
+}
+
+pub const ENGLISH: Locale = Locale {
+    name: "English",
+    weekdays: [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ],
+    short_weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    date_order: DateOrder::Mdy,
+    day_letters: ['M', 'T', 'W', 'T', 'F', 'S', 'S'],
+    alarm_title: "--- Alarm ---",
+    ringing_title: "-- Wake up! --",
+    settings_title: "-- Settings --",
+    fields: ["year", "month", "day", "hour", "minute"],
+    set: "set",
+    language: "language",
+    alarm: "alarm",
+    alarm_off: "off",
+    alarm_once: "once",
+    alarm_empty: "empty",
+    no_alarms: "no alarms",
+    snooze: "snooze",
+};
+
+// the accents need the ISO 8859-1 fonts in the display
+pub const SPANISH: Locale = Locale {
+    name: "Español",
+    weekdays: [
+        "lunes",
+        "martes",
+        "miércoles",
+        "jueves",
+        "viernes",
+        "sábado",
+        "domingo",
+    ],
+    short_weekdays: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+    months: [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    date_order: DateOrder::Dmy,
+    // the X of miércoles is the usual letter, the M is the one of Monday
+    day_letters: ['L', 'M', 'X', 'J', 'V', 'S', 'D'],
+    alarm_title: "--- Alarma ---",
+    ringing_title: "-- ¡Arriba! --",
+    settings_title: "-- Ajustes --",
+    fields: ["año", "mes", "día", "hora", "minuto"],
+    set: "fijar",
+    language: "idioma",
+    alarm: "alarma",
+    alarm_off: "apagada",
+    alarm_once: "una vez",
+    alarm_empty: "vacía",
+    no_alarms: "sin alarmas",
+    snooze: "posponer",
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names_by_day_and_month() {
+        assert_eq!(ENGLISH.weekday(DayOfWeek::Monday), "Monday");
+        assert_eq!(SPANISH.weekday(DayOfWeek::Wednesday), "miércoles");
+        assert_eq!(SPANISH.short_weekday(DayOfWeek::Sunday), "dom");
+        assert_eq!(ENGLISH.month(1), "January");
+        assert_eq!(SPANISH.month(12), "diciembre");
+        assert_eq!(ENGLISH.month(0), "");
+        assert_eq!(ENGLISH.month(13), "");
+    }
+
+    #[test]
+    fn abbreviations_are_the_start_of_the_names() {
+        for locale in [&ENGLISH, &SPANISH].iter() {
+            for (name, short) in locale.weekdays.iter().zip(locale.short_weekdays.iter()) {
+                assert!(name.starts_with(short), "{} {}", name, short);
+            }
+        }
+    }
+
+    #[test]
+    fn every_text_is_translated() {
+        for locale in [&ENGLISH, &SPANISH].iter() {
+            let texts = [
+                locale.alarm_title,
+                locale.ringing_title,
+                locale.settings_title,
+                locale.set,
+                locale.language,
+                locale.alarm,
+                locale.alarm_off,
+                locale.alarm_once,
+                locale.alarm_empty,
+                locale.no_alarms,
+                locale.snooze,
+            ];
+            for text in texts.iter().chain(locale.fields.iter()) {
+                assert!(!text.is_empty(), "{}", locale.name);
+            }
+        }
+    }
+
+    #[test]
+    fn languages_cycle() {
+        let language = Language::default();
+        assert_eq!(language.locale().name, "English");
+        assert_eq!(language.next().locale().name, "Español");
+        assert_eq!(language.next().next(), language);
+    }
+}
@@ -11,37 +11,107 @@
 //----------------------------------------------------------------------------
 #![deny(unsafe_code)]
 // #![deny(warnings)]
-#![no_main]
-#![no_std]
+#![cfg_attr(not(test), no_main)]
+#![cfg_attr(not(test), no_std)]
 
+mod alarm;
+mod backup;
 mod buttons;
+mod console;
 mod datetime;
+mod format;
 mod io;
+mod locale;
+mod power;
+mod ramp;
+mod timezone;
+mod tone;
 mod ui;
 
-use crate::buttons::Button;
-use crate::io::Logger;
-use datetime::DateTime;
-use heapless::String;
+#[cfg(not(test))]
 use panic_semihosting as _;
+#[cfg(not(test))]
 use rtic::app;
-use stm32f1xx_hal::gpio::PinState;
-use stm32f1xx_hal::{gpio, pac, prelude::*};
-
-use core::fmt::Write;
-
-use pac::I2C1;
-use sh1106::{prelude::*, Builder};
-use stm32f1xx_hal::{
-    i2c::{BlockingI2c, DutyCycle, Mode},
-    rtc::Rtc,
-    serial::{Config, Serial},
-};
-use systick_monotonic::{fugit::Duration, Systick};
 
+// the app is not compiled for the host tests, only the pure modules
+#[cfg(not(test))]
 #[app(device = stm32f1xx_hal::pac, peripherals = true, dispatchers = [SPI1])]
 mod app {
-    use super::*;
+    use crate::alarm::{Alarm, Alarms, Snooze, MAX_ALARMS};
+    use crate::backup::{Backup, BootState};
+    use crate::buttons::{Button, DebounceConfig, Debouncer, Millis, Polarity, Timing};
+    use crate::console::{Command, LineBuffer, LineError, HELP, LINE_LEN};
+    use crate::datetime::{DayMask, IsoForm};
+    use crate::format::DateTimeFormat;
+    use crate::io::{BufferedTx, Level, LogError, Logger, Overflow};
+    use crate::log;
+    use crate::power::{Busy, Policy, Power, PowerState};
+    use crate::ramp::{Curve, Light, PwmLight, Ramp, FULL_LEVEL};
+    use crate::timezone::{DstRule, TimeZone};
+    use crate::tone::{Buzzer, Pattern, PwmBuzzer, Sequencer};
+    use crate::ui::{Action, ClockFSM, ClockState};
+    use heapless::String;
+    use stm32f1xx_hal::gpio::{Edge, ExtiPin, PinState};
+    use stm32f1xx_hal::{gpio, pac, prelude::*};
+
+    use core::fmt::Write;
+
+    use pac::I2C1;
+    use sh1106::{prelude::*, Builder};
+    use stm32f1xx_hal::{
+        flash::ACR,
+        i2c::{BlockingI2c, DutyCycle, Mode},
+        rcc::CFGR,
+        rtc::Rtc,
+        serial::{Config, Serial},
+    };
+    use systick_monotonic::{fugit::Duration, Systick};
+
+    //-------------------------------------------------------------------------
+    //                        configuration
+    //-------------------------------------------------------------------------
+    /// time used to seed the RTC when the backup domain lost its power: 2023-04-11 00:00:00 UTC
+    const FIRST_BOOT_EPOCH: u32 = 1681171200;
+    /// the RTC is in UTC, the time on the display and the alarms are in this zone
+    const TIME_ZONE: TimeZone = TimeZone::new(-180, DstRule::None);
+    /// the MCU goes to the stop mode when the display is turned off after a minute without use, the
+    /// USART does not wake it up from there so the console only works while the display is on
+    const POWER_POLICY: Policy = Policy::Stop;
+    /// the debounce of the three buttons, the `Timed` one reads the SysTick monotonic. The
+    /// `debounce` command of the console changes it until the next reset
+    const DEBOUNCE: DebounceConfig = DebounceConfig::Counter(15);
+    /// the buttons of this board switch to ground with the pull up of the pins, a board with
+    /// pull down inputs or touch sensors is `ActiveHigh`
+    const BUTTON_POLARITY: Polarity = Polarity::ActiveLow;
+    const DISPLAY_TIMEOUT_SECONDS: u16 = 60;
+    /// the logs wait here while the UART sends them, at 9600 bauds this is half a second of logs
+    const LOG_BUFFER: usize = 512;
+    /// the logs below it are not sent until the `log` command of the console changes it
+    const LOG_LEVEL: Level = Level::Info;
+    /// when the buffer is full the last logs are more useful than the first ones
+    const LOG_OVERFLOW: Overflow = Overflow::DropOldest;
+    /// Up or Down snooze the alarm 9 minutes three times, it stops after 5 minutes ringing
+    const SNOOZE: Snooze = Snooze {
+        minutes: 9,
+        max_count: 3,
+        ring_timeout: 300,
+    };
+    /// Up and Down repeat while held: after 500 ms, then from every 200 ms up to every 50 ms
+    const ARROW_TIMING: Timing = Timing {
+        long_press: 0,
+        double_click: 0,
+        repeat_delay: 50,
+        repeat_interval: 20,
+        repeat_min: 5,
+    };
+    /// a long press of 800 ms in the Enter button is a Back
+    const ENTER_TIMING: Timing = Timing {
+        long_press: 80,
+        double_click: 0,
+        repeat_delay: 0,
+        repeat_interval: 0,
+        repeat_min: 0,
+    };
     //-------------------------------------------------------------------------
     //                        type alias
     //-------------------------------------------------------------------------
@@ -52,6 +122,9 @@ mod app {
     type ButtonDownPin = gpio::gpioa::PA6<gpio::Input<gpio::PullUp>>;
     type ButtonEnterPin = gpio::gpioa::PA7<gpio::Input<gpio::PullUp>>;
     type OledDisplay = GraphicsMode<I2cInterface<BlockingI2c<I2C1, (Scl, Sda)>>>;
+    type BackupRegisters = stm32f1xx_hal::backup_domain::BackupDomain;
+    type SerialRx = stm32f1xx_hal::serial::Rx<pac::USART1>;
+    type SerialLogger = Logger<BufferedTx<pac::USART1, LOG_BUFFER>>;
 
     #[monotonic(binds = SysTick, default = true)]
     type MonoTimer = Systick<1000>;
@@ -62,16 +135,29 @@ mod app {
     #[shared]
     struct Shared {
         led: Led,
+        rtc: Rtc,
+        alarms: Alarms,
+        backup: Backup<BackupRegisters>,
+        power: Power,
+        exti: pac::EXTI,
+        // a debounce from the console, the buttons take it when they are idle
+        new_debounce: Option<DebounceConfig>,
+        tone: Sequencer,
+        buzzer: PwmBuzzer,
+        light: PwmLight,
+        display: OledDisplay,
+        logger: SerialLogger,
     }
 
     #[local]
     struct Local {
-        button_up: Button<ButtonUpPin>,
-        button_down: Button<ButtonDownPin>,
-        button_enter: Button<ButtonEnterPin>,
-        rtc: Rtc,
-        display: OledDisplay,
-        logger: Logger,
+        button_up: Button<ButtonUpPin, Debouncer>,
+        button_down: Button<ButtonDownPin, Debouncer>,
+        button_enter: Button<ButtonEnterPin, Debouncer>,
+        scb: pac::SCB,
+        pwr: pac::PWR,
+        acr: ACR,
+        rx: SerialRx,
         clock_fsm: crate::ui::ClockFSM,
     }
 
@@ -87,12 +173,7 @@ mod app {
         let mut pwr = cx.device.PWR;
         let mut flash = cx.device.FLASH.constrain();
         // let clocks = rcc.cfgr.freeze(&mut flash.acr);
-        let clocks = rcc
-            .cfgr
-            .use_hse(8.MHz())
-            .sysclk(36.MHz())
-            .pclk1(36.MHz())
-            .freeze(&mut flash.acr);
+        let clocks = clock_config().freeze(&mut flash.acr);
 
         // let clocks = rcc
         //     .cfgr
@@ -110,6 +191,22 @@ mod app {
             .pc13
             .into_push_pull_output_with_state(&mut gpioc.crh, PinState::Low);
 
+        // the buzzer is in PA0, the frequency changes with each note
+        let buzzer_pin = gpioa.pa0.into_alternate_push_pull(&mut gpioa.crl);
+        let buzzer = PwmBuzzer::new(cx.device.TIM2.pwm_hz(
+            buzzer_pin,
+            &mut afio.mapr,
+            1.kHz(),
+            &clocks,
+        ));
+        // the wake up light is a LED in PB0
+        let light_pin = gpiob.pb0.into_alternate_push_pull(&mut gpiob.crl);
+        let mut light: PwmLight =
+            cx.device
+                .TIM3
+                .pwm_hz(light_pin, &mut afio.mapr, 1.kHz(), &clocks);
+        light.set_brightness(0);
+
         // USART1
         let tx = gpiob.pb6.into_alternate_push_pull(&mut gpiob.crl);
         let rx = gpiob.pb7;
@@ -120,8 +217,13 @@ mod app {
             Config::default().baudrate(9600.bps()),
             &clocks,
         );
-        let tx = serial.split().0;
-        let logger = Logger::new(tx);
+        let (tx, mut rx) = serial.split();
+        let mut logger = Logger::new(BufferedTx::new(tx, LOG_OVERFLOW))
+            .with_level(LOG_LEVEL)
+            .with_timestamps(true);
+        // the USART does not receive in the stop mode, the console works
+        // while the display is on and each received byte keeps it on
+        rx.listen();
         // oled display pins
         let scl = gpiob.pb8.into_alternate_open_drain(&mut gpiob.crh);
         let sda = gpiob.pb9.into_alternate_open_drain(&mut gpiob.crh);
@@ -147,42 +249,86 @@ mod app {
         display.init().ok();
         display.flush().ok();
         let systick = cx.core.SYST;
-        // let mono = Systick::new(systick, 36_000_000);
-        let mono = Systick::new(systick, 8_000_000);
+        let mono = Systick::new(systick, clocks.sysclk().to_Hz());
 
-        let button_up_pin = gpioa.pa5.into_pull_up_input(&mut gpioa.crl);
-        let button_down_pin = gpioa.pa6.into_pull_up_input(&mut gpioa.crl);
-        let button_enter_pin = gpioa.pa7.into_pull_up_input(&mut gpioa.crl);
+        let mut button_up_pin = gpioa.pa5.into_pull_up_input(&mut gpioa.crl);
+        let mut button_down_pin = gpioa.pa6.into_pull_up_input(&mut gpioa.crl);
+        let mut button_enter_pin = gpioa.pa7.into_pull_up_input(&mut gpioa.crl);
+        // the buttons are polled only after an edge in one of them
+        let mut exti = cx.device.EXTI;
+        button_up_pin.make_interrupt_source(&mut afio);
+        button_up_pin.trigger_on_edge(&mut exti, Edge::RisingFalling);
+        button_up_pin.enable_interrupt(&mut exti);
+        button_down_pin.make_interrupt_source(&mut afio);
+        button_down_pin.trigger_on_edge(&mut exti, Edge::RisingFalling);
+        button_down_pin.enable_interrupt(&mut exti);
+        button_enter_pin.make_interrupt_source(&mut afio);
+        button_enter_pin.trigger_on_edge(&mut exti, Edge::RisingFalling);
+        button_enter_pin.enable_interrupt(&mut exti);
+        // the RTC keeps counting across resets, it is seeded only when
+        // the backup domain was reset, after that the time is set from the buttons
         let mut rtc = Rtc::new(cx.device.RTC, &mut backup_domain);
-        let today = DateTime {
-            year: 2023,
-            month: 4,
-            day: 11,
-            hour: 22,
-            min: 18,
-            sec: 00,
-            day_of_week: datetime::DayOfWeek::Saturday,
-        };
-        if let Some(epoch) = today.to_epoch() {
-            rtc.set_time(epoch);
+        let mut backup = Backup::new(backup_domain);
+        if backup.boot_state() == BootState::Unconfigured {
+            rtc.set_time(FIRST_BOOT_EPOCH);
         }
-
         rtc.listen_seconds();
+        // the second interrupt does not wake up the MCU from the stop
+        // mode, the RTC alarm (EXTI line 17) is armed with the next alarm to do that
+        rtc.listen_alarm();
+        exti.imr.modify(|_, w| w.mr17().set_bit());
+        exti.rtsr.modify(|_, w| w.tr17().set_bit());
+
+        let now = rtc.current_time();
+        logger.set_time(now);
+        let mut alarms = backup.load_alarms(now).unwrap_or_else(|| {
+            let mut alarms = Alarms::new();
+            alarms
+                .add(
+                    Alarm::repeating(7, 0, DayMask::WEEKDAYS)
+                        .with_ramp(Ramp::new(10, Curve::Exponential)),
+                    now,
+                )
+                .ok();
+            backup.save_alarms(&alarms);
+            backup.mark_configured();
+            alarms
+        });
+        alarms.set_zone(TIME_ZONE, now);
 
         // NOTE(elsuizo:2021-11-24): here we dont need a super fast spawn(for the inititlization...)!!!
         // NOTE(elsuizo: 2023-04-11): this is one second
         react::spawn_after(Duration::<u64, 1, 1000>::from_ticks(1000)).unwrap();
 
         (
-            Shared { led },
-            Local {
-                button_up: Button::new(button_up_pin),
-                button_down: Button::new(button_down_pin),
-                button_enter: Button::new(button_enter_pin),
+            Shared {
+                led,
                 rtc,
+                alarms,
+                backup,
+                power: Power::new(POWER_POLICY, DISPLAY_TIMEOUT_SECONDS),
+                exti,
+                new_debounce: None,
+                tone: Sequencer::new(),
+                buzzer,
+                light,
                 display,
                 logger,
-                clock_fsm: crate::ui::ClockFSM::init(crate::ui::ClockState::Time),
+            },
+            Local {
+                button_up: Button::with_debounce(button_up_pin, debounce(), ARROW_TIMING)
+                    .with_polarity(BUTTON_POLARITY),
+                button_down: Button::with_debounce(button_down_pin, debounce(), ARROW_TIMING)
+                    .with_polarity(BUTTON_POLARITY),
+                button_enter: Button::with_debounce(button_enter_pin, debounce(), ENTER_TIMING)
+                    .with_polarity(BUTTON_POLARITY),
+                scb: cx.core.SCB,
+                pwr,
+                acr: flash.acr,
+                rx,
+                clock_fsm: ClockFSM::init(ClockState::Time)
+                    .with_snooze(SNOOZE)
+                    .with_zone(TIME_ZONE),
             },
             init::Monotonics(mono),
         )
@@ -191,76 +337,596 @@ mod app {
     //-------------------------------------------------------------------------
     //                        tasks
     //-------------------------------------------------------------------------
-    #[idle]
-    fn idle(_: idle::Context) -> ! {
+    #[idle(local = [scb, pwr, acr], shared = [power, logger])]
+    fn idle(cx: idle::Context) -> ! {
+        let idle::LocalResources { scb, pwr, acr } = cx.local;
+        let idle::SharedResources {
+            mut power,
+            mut logger,
+        } = cx.shared;
         loop {
-            continue;
+            // with the interrupts disabled the wfi still wakes up, and
+            // the interrupt runs after the clocks are restored
+            cortex_m::interrupt::free(|_| {
+                let sending = logger.lock(|logger| !logger.writer().is_idle());
+                let mode = power.lock(|power| {
+                    power.set_busy(Busy::Log, sending);
+                    power.sleep_mode()
+                });
+                crate::power::enter_low_power(mode, scb, pwr, || {
+                    clock_config().freeze(acr);
+                });
+            });
         }
     }
+    // an edge in any of the buttons (PA5, PA6, PA7) starts the polling
+    #[task(binds = EXTI9_5, shared = [exti])]
+    fn button_edge(mut cx: button_edge::Context) {
+        cx.shared.exti.lock(|exti| {
+            exti.pr
+                .write(|w| w.pr5().set_bit().pr6().set_bit().pr7().set_bit())
+        });
+        // this fails if the polling is already running, and that is ok
+        react::spawn().ok();
+    }
+
     // NOTE(elsuizo:2021-11-24): the maximum period of this periodic task for a responsive button
     // action is 13 ms
     // NOTE(elsuizo:2021-11-21): remember that the method set_low() needs the trait: `use embedded_hal::digital::v2::OutputPin;`
     // to be used!!!
-    #[task(local = [button_up, button_down, button_enter], shared = [led])]
-    fn react(cx: react::Context) {
-        use crate::buttons::PinState::*;
-        use crate::ui::ClockState::*;
-        use crate::ui::Msg::*;
+    #[task(local = [button_up, button_down, button_enter], shared = [led, power, new_debounce])]
+    fn react(mut cx: react::Context) {
+        use crate::buttons::Event::*;
+        use crate::ui::Msg;
 
-        if let PinUp = cx.local.button_up.poll() {
-            dispatch_msg::spawn(Up).ok();
+        let up = match cx.local.button_up.event() {
+            Press | Repeat => Some(Msg::Up),
+            _ => None,
+        };
+        let down = match cx.local.button_down.event() {
+            Press | Repeat => Some(Msg::Down),
+            _ => None,
+        };
+        let enter = match cx.local.button_enter.event() {
+            Click => Some(Msg::Enter),
+            LongPress => Some(Msg::Back),
+            _ => None,
+        };
+        for &msg in [up, down, enter].iter().flatten() {
+            dispatch_msg::spawn(msg).ok();
         }
-        if let PinUp = cx.local.button_down.poll() {
-            dispatch_msg::spawn(Down).ok();
+        let local = cx.local;
+        let idle = local.button_up.is_idle()
+            && local.button_down.is_idle()
+            && local.button_enter.is_idle();
+        // keep polling while any button is in motion, the next edge starts it again
+        if !idle {
+            react::spawn_after(Duration::<u64, 1, 1000>::from_ticks(10)).ok();
+        } else if let Some(config) = cx.shared.new_debounce.lock(|config| config.take()) {
+            local.button_up.set_debounce(config.debouncer(now_ms));
+            local.button_down.set_debounce(config.debouncer(now_ms));
+            local.button_enter.set_debounce(config.debouncer(now_ms));
         }
+        // the polling needs the SysTick, that does not run in the stop mode
+        cx.shared
+            .power
+            .lock(|power| power.set_busy(Busy::Buttons, !idle));
+    }
 
-        if let Nothing = cx.local.button_up.poll() {
-            dispatch_msg::spawn(Continue).ok();
-        }
+    // it only wakes up the MCU from the stop mode, the alarm is
+    // checked in `rtc_tick`
+    #[task(binds = RTCALARM, shared = [exti])]
+    fn rtc_alarm(mut cx: rtc_alarm::Context) {
+        cx.shared
+            .exti
+            .lock(|exti| exti.pr.write(|w| w.pr17().set_bit()));
+    }
 
-        if let Nothing = cx.local.button_down.poll() {
-            dispatch_msg::spawn(Continue).ok();
+    // the RTC second interrupt is the time base for the alarms
+    #[task(binds = RTC, local = [armed: Option<u32> = None, ramping: bool = false], shared = [rtc, alarms, led, backup, power, tone, light, logger])]
+    fn rtc_tick(cx: rtc_tick::Context) {
+        let rtc_tick::SharedResources {
+            mut rtc,
+            mut alarms,
+            mut led,
+            mut backup,
+            mut power,
+            mut tone,
+            mut light,
+            mut logger,
+        } = cx.shared;
+        let now = rtc.lock(|rtc| {
+            rtc.clear_second_flag();
+            rtc.clear_alarm_flag();
+            rtc.current_time()
+        });
+        logger.lock(|logger| {
+            logger.set_time(now);
+            // the lost bytes are reported once per second at most
+            let lost = logger.writer_mut().take_dropped();
+            if lost > 0 {
+                log!(logger, Level::Warn, "log", "{} bytes lost", lost).ok();
+            }
+        });
+        let fired = (&mut alarms, &mut backup).lock(|alarms, backup| {
+            let fired = alarms.check(now);
+            if let Some(index) = fired {
+                // the one shot alarms are disabled after ringing
+                if alarms.get(index).is_some_and(|alarm| alarm.is_one_shot()) {
+                    backup.save_alarms(alarms);
+                }
+            }
+            fired
+        });
+        if let Some(index) = fired {
+            led.lock(|l| l.set_low());
+            power.lock(|power| power.activity());
+            light.lock(|light| light.set_brightness(FULL_LEVEL));
+            tone.lock(|tone| {
+                tone.set_volume(FULL_LEVEL);
+                tone.start(Pattern::BeepBeep, true)
+            });
+            // this fails if the tone is already playing, and the new pattern continues there
+            tone_step::spawn().ok();
+            dispatch_msg::spawn(crate::ui::Msg::Ring(index)).ok();
+            *cx.local.ramping = false;
+        } else {
+            power.lock(|power| power.tick());
+        }
+        // the wake up ramp goes with the seconds until the alarm
+        let levels = alarms.lock(|alarms| alarms.ramp(now));
+        if let Some(levels) = levels {
+            light.lock(|light| light.set_brightness(levels.light));
+            let start = tone.lock(|tone| {
+                tone.set_volume(levels.sound);
+                let start = levels.sound > 0 && !tone.is_playing();
+                if start {
+                    tone.start(Pattern::Rising, true);
+                }
+                start
+            });
+            if start {
+                tone_step::spawn().ok();
+            }
+            *cx.local.ramping = true;
+        } else if *cx.local.ramping {
+            // the alarm was disabled or the time changed in the middle of the ramp
+            light.lock(|light| light.set_brightness(0));
+            tone.lock(|tone| tone.stop());
+            *cx.local.ramping = false;
         }
+        power.lock(|power| power.set_busy(Busy::Ramp, levels.is_some()));
+        // the RTC alarm wakes up the MCU from the stop mode
+        let next = alarms.lock(|alarms| alarms.next_wake_up(now));
+        if next != *cx.local.armed {
+            if let Some(epoch) = next {
+                rtc.lock(|rtc| rtc.set_alarm(epoch));
+            }
+            *cx.local.armed = next;
+        }
+        // the buttons are not polled all the time anymore, so the clock is refreshed here
+        dispatch_msg::spawn(crate::ui::Msg::Continue).ok();
+    }
 
-        // if let PinUp = cx.local.button_enter.poll() {
-        //     dispatch_msg::spawn(Enter).ok();
-        // }
-        react::spawn_after(Duration::<u64, 1, 1000>::from_ticks(10)).unwrap();
+    // plays one note of the sequencer and waits its duration
+    #[task(shared = [tone, buzzer, power])]
+    fn tone_step(cx: tone_step::Context) {
+        let tone_step::SharedResources {
+            tone,
+            mut buzzer,
+            power,
+        } = cx.shared;
+        let (note, volume) = (tone, power).lock(|tone, power| {
+            let note = tone.next();
+            // the durations are measured with the SysTick
+            power.set_busy(Busy::Tone, note.is_some());
+            (note, tone.volume())
+        });
+        match note {
+            Some(note) => {
+                buzzer.lock(|buzzer| {
+                    buzzer.set_volume(volume);
+                    buzzer.play(note)
+                });
+                let duration = u64::from(note.duration);
+                tone_step::spawn_after(Duration::<u64, 1, 1000>::from_ticks(duration)).ok();
+            }
+            None => buzzer.lock(|buzzer| buzzer.mute()),
+        }
     }
 
-    #[task(local = [display, logger, clock_fsm, rtc], shared = [led])]
+    // the screen is sent over the (blocking) I2C only when it changes
+    #[task(
+        capacity = 4,
+        local = [clock_fsm, frame: Option<(ClockState, String<256>)> = None, log_failures: u32 = 0],
+        shared = [led, rtc, alarms, backup, power, tone, buzzer, light, display, logger]
+    )]
     fn dispatch_msg(cx: dispatch_msg::Context, msg: crate::ui::Msg) {
         use crate::ui::Msg::*;
-        let dispatch_msg::SharedResources { mut led } = cx.shared;
-        cx.local.display.clear();
-        cx.local.clock_fsm.next_state(msg);
+        let dispatch_msg::SharedResources {
+            mut led,
+            mut rtc,
+            mut alarms,
+            mut backup,
+            mut power,
+            mut tone,
+            mut buzzer,
+            mut light,
+            mut display,
+            mut logger,
+        } = cx.shared;
+        // a button that turns on the display does nothing else
+        let msg = match msg {
+            Continue | Ring(_) => msg,
+            _ => match power.lock(|power| power.activity()) {
+                Some(PowerState::Active) => Continue,
+                _ => msg,
+            },
+        };
+        let frame = cx.local.frame;
+        if power.lock(|power| power.state()) == PowerState::DisplayOff {
+            // the sh1106 crate does not expose the display off command, the screen is only
+            // cleared: no pixel is lit but the controller and its charge pump stay on
+            if frame.take().is_some() {
+                display.lock(|display| {
+                    display.clear();
+                    display.flush().ok();
+                });
+            }
+            return;
+        }
+        let failures = cx.local.log_failures;
+        let mut now = rtc.lock(|rtc| rtc.current_time());
+        match cx.local.clock_fsm.next_state(msg, now) {
+            Some(Action::SetTime(epoch)) => {
+                rtc.lock(|rtc| rtc.set_time(epoch));
+                alarms.lock(|alarms| alarms.rearm(epoch));
+                let date = TIME_ZONE.datetime(epoch);
+                let date = DateTimeFormat::LOG.display(&date);
+                log_failed(
+                    failures,
+                    logger.lock(|logger| log!(logger, Level::Info, "ui", "time set to {}", date)),
+                );
+                now = epoch;
+            }
+            Some(Action::ToggleAlarm(index)) => {
+                (&mut alarms, &mut backup).lock(|alarms, backup| {
+                    if let Some(&alarm) = alarms.get(index) {
+                        let alarm = Alarm {
+                            enabled: !alarm.enabled,
+                            ..alarm
+                        };
+                        alarms.set(index, alarm, now);
+                        backup.save_alarms(alarms);
+                    }
+                });
+            }
+            Some(Action::Snooze(index, until)) => {
+                // without the mute the note that is playing goes on until its end
+                (&mut tone, &mut buzzer).lock(|tone, buzzer| {
+                    tone.stop();
+                    buzzer.mute();
+                });
+                light.lock(|light| light.set_brightness(0));
+                alarms.lock(|alarms| alarms.snooze(index, until));
+                let until = TIME_ZONE.datetime(until);
+                let until = until.iso(IsoForm::Time);
+                let logged = logger.lock(|logger| {
+                    log!(
+                        logger,
+                        Level::Info,
+                        "alarm",
+                        "{} snoozed until {}",
+                        index,
+                        until
+                    )
+                });
+                log_failed(failures, logged);
+            }
+            Some(Action::Dismiss(index)) => {
+                (&mut tone, &mut buzzer).lock(|tone, buzzer| {
+                    tone.stop();
+                    buzzer.mute();
+                });
+                light.lock(|light| light.set_brightness(0));
+                alarms.lock(|alarms| alarms.dismiss());
+                let logged =
+                    logger.lock(|logger| log!(logger, Level::Info, "alarm", "{} dismissed", index));
+                log_failed(failures, logged);
+            }
+            None => {}
+        }
+        let clock_fsm = cx.local.clock_fsm;
+        let text = alarms.lock(|alarms| screen_text(clock_fsm, now, alarms));
         match msg {
             Up => {
                 led.lock(|l| l.toggle());
-                let mut time: String<256> = String::new();
-                let datetime = DateTime::new(cx.local.rtc.current_time());
-                write!(&mut time, "{}", datetime).unwrap();
-                cx.local.logger.log("button Up pressed!!!").ok();
-                crate::ui::draw_menu(cx.local.display, cx.local.clock_fsm.state, Some(&time)).ok();
-                cx.local.display.flush().unwrap();
+                log_failed(
+                    failures,
+                    logger.lock(|logger| log!(logger, Level::Debug, "ui", "button Up pressed")),
+                );
             }
             Down => {
                 led.lock(|l| l.toggle());
-                cx.local.logger.log("button Down pressed!!!").ok();
-                let mut time: String<256> = String::new();
-                let datetime = DateTime::new(cx.local.rtc.current_time());
-                write!(&mut time, "{}", datetime).unwrap();
-                crate::ui::draw_menu(cx.local.display, cx.local.clock_fsm.state, Some(&time)).ok();
-                cx.local.display.flush().unwrap();
+                log_failed(
+                    failures,
+                    logger.lock(|logger| log!(logger, Level::Debug, "ui", "button Down pressed")),
+                );
+            }
+            Enter => {
+                log_failed(
+                    failures,
+                    logger.lock(|logger| log!(logger, Level::Debug, "ui", "button Enter pressed")),
+                );
+            }
+            Back => {
+                let logged = logger
+                    .lock(|logger| log!(logger, Level::Debug, "ui", "button Enter long pressed"));
+                log_failed(failures, logged);
             }
             Continue => {
                 // led.lock(|l| l.toggle());
-                let mut time: String<256> = String::new();
-                let datetime = DateTime::new(cx.local.rtc.current_time());
-                write!(&mut time, "{}", datetime).unwrap();
-                crate::ui::draw_menu(cx.local.display, cx.local.clock_fsm.state, Some(&time)).ok();
-                cx.local.display.flush().unwrap();
+            }
+            Ring(index) => {
+                let date = TIME_ZONE.datetime(now);
+                let date = date.iso(IsoForm::DateTime);
+                let logged = logger.lock(|logger| {
+                    log!(
+                        logger,
+                        Level::Info,
+                        "alarm",
+                        "{} ringing at {}",
+                        index,
+                        date
+                    )
+                });
+                log_failed(failures, logged);
+            }
+        };
+        // the failed lines are reported when the logger works again
+        if *failures > 0 {
+            let count = *failures;
+            let reported = logger.lock(|logger| {
+                log!(
+                    logger,
+                    Level::Error,
+                    "log",
+                    "{} lines could not be sent",
+                    count
+                )
+            });
+            if reported.is_ok() {
+                *failures = 0;
+            }
+        }
+        if frame.as_ref() == Some(&(clock_fsm.state, text.clone())) {
+            return;
+        }
+        display.lock(|display| {
+            display.clear();
+            crate::ui::draw_menu(display, clock_fsm.state, Some(&text), clock_fsm.locale()).ok();
+            display.flush().ok();
+        });
+        *frame = Some((clock_fsm.state, text));
+    }
+
+    // the received bytes are buffered in the interrupt and every line
+    // is parsed and executed in `run_command`, the logs are sent from here too
+    #[task(binds = USART1, local = [rx, line: LineBuffer = LineBuffer::new()], shared = [power, logger])]
+    fn serial(cx: serial::Context) {
+        let serial::SharedResources {
+            mut power,
+            mut logger,
+        } = cx.shared;
+        // an overrun error is cleared by the read and the line continues without those bytes
+        while let Ok(byte) = cx.local.rx.read() {
+            // the typing is a use, so the MCU stays in the sleep mode that receives the next bytes
+            power.lock(|power| power.activity());
+            if let Some(line) = cx.local.line.push(byte) {
+                // this fails if the commands arrive faster than they run, and they are lost
+                run_command::spawn(line).ok();
+            }
+        }
+        logger.lock(|logger| logger.writer_mut().drain());
+    }
+
+    #[task(capacity = 2, shared = [rtc, alarms, backup, power, new_debounce, display, logger])]
+    fn run_command(cx: run_command::Context, line: Result<String<LINE_LEN>, LineError>) {
+        const TAG: &str = "console";
+        let run_command::SharedResources {
+            mut rtc,
+            mut alarms,
+            mut backup,
+            mut power,
+            mut new_debounce,
+            mut display,
+            mut logger,
+        } = cx.shared;
+        power.lock(|power| power.activity());
+        let now = rtc.lock(|rtc| rtc.current_time());
+        let command = match line {
+            Ok(line) => Command::parse(&line, &TIME_ZONE.datetime(now)),
+            Err(LineError::TooLong) => {
+                logger.lock(|logger| log!(logger, Level::Warn, TAG, "the line is too long").ok());
+                return;
+            }
+        };
+        match command {
+            Ok(Command::Help) => {
+                logger.lock(|logger| log!(logger, Level::Info, TAG, "{}", HELP).ok());
+            }
+            Ok(Command::TimeGet) => {
+                let date = TIME_ZONE.datetime(now);
+                let date = DateTimeFormat::CONSOLE.display(&date);
+                logger.lock(|logger| log!(logger, Level::Info, TAG, "{}", date).ok());
+            }
+            Ok(Command::TimeSet(date)) => {
+                let epoch = date
+                    .to_epoch()
+                    .ok()
+                    .and_then(|local| TIME_ZONE.to_utc(local));
+                if let Some(epoch) = epoch {
+                    rtc.lock(|rtc| rtc.set_time(epoch));
+                    alarms.lock(|alarms| alarms.rearm(epoch));
+                }
+                let (level, result) = match epoch {
+                    Some(_) => (Level::Info, "time set to"),
+                    None => (Level::Error, "out of range:"),
+                };
+                let date = date.iso(IsoForm::DateTime);
+                logger.lock(|logger| log!(logger, level, TAG, "{} {}", result, date).ok());
+            }
+            Ok(Command::AlarmList) => {
+                (&mut alarms, &mut logger).lock(|alarms, logger| {
+                    for (index, alarm) in alarms.iter().enumerate() {
+                        log!(logger, Level::Info, TAG, "alarm {}: {}", index + 1, alarm).ok();
+                    }
+                    if alarms.is_empty() {
+                        log!(logger, Level::Info, TAG, "no alarms").ok();
+                    }
+                });
+            }
+            Ok(Command::AlarmAdd(alarm)) => {
+                let added = (&mut alarms, &mut backup).lock(|alarms, backup| {
+                    let index = alarms.add(alarm, now).ok()?;
+                    backup.save_alarms(alarms);
+                    Some(index)
+                });
+                logger
+                    .lock(|logger| match added {
+                        Some(index) => {
+                            log!(logger, Level::Info, TAG, "alarm {}: {}", index + 1, alarm)
+                        }
+                        None => log!(logger, Level::Error, TAG, "already {} alarms", MAX_ALARMS),
+                    })
+                    .ok();
+            }
+            Ok(Command::AlarmDel(index)) => {
+                let removed = (&mut alarms, &mut backup).lock(|alarms, backup| {
+                    // the indices of the ringing and the snoozed alarm would point to another one
+                    if alarms.ringing().is_some() || alarms.snoozed().is_some() {
+                        return Err("can not be deleted until the ringing alarm is dismissed");
+                    }
+                    let alarm = alarms.remove(index).ok_or("does not exist")?;
+                    backup.save_alarms(alarms);
+                    Ok(alarm)
+                });
+                let number = index + 1;
+                logger
+                    .lock(|logger| match removed {
+                        Ok(alarm) => log!(
+                            logger,
+                            Level::Info,
+                            TAG,
+                            "alarm {} deleted: {}",
+                            number,
+                            alarm
+                        ),
+                        Err(reason) => {
+                            log!(logger, Level::Error, TAG, "alarm {} {}", number, reason)
+                        }
+                    })
+                    .ok();
+            }
+            Ok(Command::Bright(level)) => {
+                let done = display.lock(|display| display.set_contrast(level).is_ok());
+                let severity = if done { Level::Info } else { Level::Error };
+                logger.lock(|logger| log!(logger, severity, TAG, "bright {}", level).ok());
+            }
+            Ok(Command::Debounce(config)) => {
+                new_debounce.lock(|new_debounce| *new_debounce = Some(config));
+                // the buttons are idle now, or they take it after the current press
+                react::spawn().ok();
+                logger.lock(|logger| log!(logger, Level::Info, TAG, "debounce changed").ok());
+            }
+            Ok(Command::LogLevel(level)) => {
+                logger.lock(|logger| {
+                    logger.set_level(level);
+                    log!(logger, Level::Info, TAG, "log level {:?}", level).ok()
+                });
+            }
+            Ok(Command::Reboot) => {
+                logger.lock(|logger| {
+                    log!(logger, Level::Warn, TAG, "rebooting").ok();
+                    logger.writer_mut().bflush();
+                });
+                cortex_m::peripheral::SCB::sys_reset();
+            }
+            Err(error) => {
+                logger.lock(|logger| log!(logger, Level::Error, TAG, "{}", error).ok());
+            }
+        }
+    }
+
+    fn debounce() -> Debouncer {
+        DEBOUNCE.debouncer(now_ms)
+    }
+
+    /// the milliseconds of the SysTick monotonic
+    fn now_ms() -> Millis {
+        monotonics::now().ticks() as Millis
+    }
+
+    /// the clocks of the init, also restored after the stop mode
+    fn clock_config() -> CFGR {
+        CFGR::default()
+            .use_hse(8.MHz())
+            .sysclk(36.MHz())
+            .pclk1(36.MHz())
+    }
+
+    /// counts the lines of `dispatch_msg` that could not be sent. With `Overflow::DropOldest` the
+    /// buffer never fails and only the formatting errors are counted here, the lost bytes are
+    /// reported by `rtc_tick`
+    fn log_failed<E>(failures: &mut u32, logged: Result<(), LogError<E>>) {
+        if logged.is_err() {
+            *failures = failures.saturating_add(1);
+        }
+    }
+
+    /// the text that goes with each state of the menu
+    fn screen_text(clock_fsm: &ClockFSM, now: u32, alarms: &Alarms) -> String<256> {
+        let mut text: String<256> = String::new();
+        let locale = clock_fsm.locale();
+        match clock_fsm.state {
+            ClockState::Time => {
+                let format = DateTimeFormat::display_in(clock_fsm.language);
+                let date = clock_fsm.zone.datetime(now);
+                format.write(&mut text, &date).ok()
+            }
+            ClockState::Alarm => {
+                // show the alarm that rings first or the first one if all are disabled
+                let index = alarms.next_trigger().map_or(0, |(index, _)| index);
+                match alarms.get(index) {
+                    Some(alarm) => alarm.write(&mut text, locale).ok(),
+                    None => text.push_str(locale.no_alarms).ok(),
+                }
+            }
+            ClockState::Image => None,
+            ClockState::SetTime => clock_fsm.editor.write(&mut text, locale).ok(),
+            ClockState::Ringing => {
+                let ringing = clock_fsm.ringing;
+                if let Some(alarm) = alarms.get(ringing.index) {
+                    alarm.write(&mut text, locale).ok();
+                    text.push('\n').ok();
+                }
+                write!(
+                    &mut text,
+                    "{} {}/{}",
+                    locale.snooze, ringing.snoozes, clock_fsm.snooze.max_count
+                )
+                .ok()
+            }
+            ClockState::Settings => write!(&mut text, "{}\n{}", locale.language, locale.name).ok(),
+            ClockState::AlarmList => {
+                let index = clock_fsm.selected;
+                writeln!(&mut text, "{} {}/{}", locale.alarm, index + 1, MAX_ALARMS).ok();
+                match alarms.get(index) {
+                    Some(alarm) => alarm.write(&mut text, locale).ok(),
+                    None => write!(&mut text, "--:-- {}", locale.alarm_empty).ok(),
+                }
             }
         };
+        text
     }
 }
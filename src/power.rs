@@ -0,0 +1,229 @@
+/// Power management, the states are pure logic and the register pokes are in `enter_low_power`
+#[cfg(not(test))]
+use cortex_m::peripheral::SCB;
+#[cfg(not(test))]
+use stm32f1xx_hal::pac::{PWR, RTC};
+
+/// What the MCU does when there is nothing to do
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Policy {
+    /// always the sleep mode: the clocks keep running, the fastest wake up
+    Sleep,
+    /// the stop mode when the display is off: only the RTC and the EXTI lines wake it up
+    Stop,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PowerState {
+    Active,
+    DisplayOff,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SleepMode {
+    Sleep,
+    Stop,
+}
+
+/// the tasks that need the monotonic timer (SysTick stops in the stop mode)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Busy {
+    /// the polling of the buttons
+    Buttons = 1,
+    /// the sequence of a tone
+    Tone = 2,
+    /// the wake up ramp, the PWM of the light stops in the stop mode
+    Ramp = 4,
+    /// the logs waiting to be sent, the USART stops in the stop mode
+    Log = 8,
+}
+
+pub struct Power {
+    policy: Policy,
+    /// seconds without activity before the display is turned off, zero is never
+    display_timeout: u16,
+    idle_seconds: u16,
+    state: PowerState,
+    /// one bit for each `Busy` task
+    busy: u8,
+}
+
+impl Power {
+    pub fn new(policy: Policy, display_timeout: u16) -> Self {
+        Self {
+            policy,
+            display_timeout,
+            idle_seconds: 0,
+            state: PowerState::Active,
+            busy: 0,
+        }
+    }
+
+    pub fn state(&self) -> PowerState {
+        self.state
+    }
+
+    /// call it on every RTC second, returns the new state on a transition
+    pub fn tick(&mut self) -> Option<PowerState> {
+        self.idle_seconds = self.idle_seconds.saturating_add(1);
+        let timeout = self.display_timeout != 0 && self.idle_seconds >= self.display_timeout;
+        if self.state == PowerState::Active && timeout && self.busy == 0 {
+            self.state = PowerState::DisplayOff;
+            Some(self.state)
+        } else {
+            None
+        }
+    }
+
+    /// a button or an alarm, returns the new state on a transition
+    pub fn activity(&mut self) -> Option<PowerState> {
+        self.idle_seconds = 0;
+        if self.state == PowerState::DisplayOff {
+            self.state = PowerState::Active;
+            Some(self.state)
+        } else {
+            None
+        }
+    }
+
+    pub fn set_busy(&mut self, task: Busy, busy: bool) {
+        if busy {
+            self.busy |= task as u8;
+        } else {
+            self.busy &= !(task as u8);
+        }
+    }
+
+    pub fn sleep_mode(&self) -> SleepMode {
+        match (self.policy, self.state, self.busy) {
+            (Policy::Stop, PowerState::DisplayOff, 0) => SleepMode::Stop,
+            _ => SleepMode::Sleep,
+        }
+    }
+}
+
+/// wait for an interrupt in `mode`, call it with the interrupts disabled so the wake up
+/// handler runs after the clocks are restored. After the stop mode the system clock is the
+/// HSI, `restore_clocks` turns on again the HSE and the PLL, and the RTC registers are
+/// synchronized again before the handler reads them
+#[cfg(not(test))]
+pub fn enter_low_power(
+    mode: SleepMode,
+    scb: &mut SCB,
+    pwr: &mut PWR,
+    restore_clocks: impl FnOnce(),
+) {
+    match mode {
+        SleepMode::Sleep => scb.clear_sleepdeep(),
+        SleepMode::Stop => {
+            // stop mode (not standby) with the voltage regulator in low power
+            pwr.cr.modify(|_, w| w.pdds().clear_bit().lpds().set_bit());
+            scb.set_sleepdeep();
+        }
+    }
+    cortex_m::asm::wfi();
+    if mode == SleepMode::Stop {
+        scb.clear_sleepdeep();
+        restore_clocks();
+        resync_rtc();
+    }
+}
+
+/// the APB1 clock stops in the stop mode and the counter that `Rtc::current_time` reads
+/// is stale until the next synchronization. `Rtc` waits for the RSF flag but never clears
+/// it, and it does not give its registers back, so this is the only register access that is
+/// not through the hal
+#[cfg(not(test))]
+#[allow(unsafe_code)]
+fn resync_rtc() {
+    // SAFETY: it runs with the interrupts disabled, so no task is using the `Rtc`, and the
+    // other flags of the CRL are written with ones that leave them as they are
+    let rtc = unsafe { &*RTC::ptr() };
+    while rtc.crl.read().rtoff().bit_is_clear() {}
+    rtc.crl.write(|w| {
+        w.secf()
+            .set_bit()
+            .alrf()
+            .set_bit()
+            .owf()
+            .set_bit()
+            .rsf()
+            .clear_bit()
+    });
+    while rtc.crl.read().rsf().bit_is_clear() {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display_off_after_timeout() {
+        let mut power = Power::new(Policy::Sleep, 3);
+        assert_eq!(power.tick(), None);
+        assert_eq!(power.tick(), None);
+        assert_eq!(power.tick(), Some(PowerState::DisplayOff));
+        assert_eq!(power.tick(), None);
+        assert_eq!(power.state(), PowerState::DisplayOff);
+        assert_eq!(power.activity(), Some(PowerState::Active));
+        assert_eq!(power.activity(), None);
+    }
+
+    #[test]
+    fn activity_restarts_the_timeout() {
+        let mut power = Power::new(Policy::Stop, 3);
+        power.tick();
+        power.tick();
+        power.activity();
+        power.tick();
+        power.tick();
+        assert_eq!(power.state(), PowerState::Active);
+        assert_eq!(power.tick(), Some(PowerState::DisplayOff));
+    }
+
+    #[test]
+    fn zero_timeout_is_never() {
+        let mut power = Power::new(Policy::Stop, 0);
+        for _ in 0..1000 {
+            assert_eq!(power.tick(), None);
+        }
+        assert_eq!(power.sleep_mode(), SleepMode::Sleep);
+    }
+
+    #[test]
+    fn stop_only_when_display_off_and_not_busy() {
+        let mut power = Power::new(Policy::Stop, 1);
+        assert_eq!(power.sleep_mode(), SleepMode::Sleep);
+        power.set_busy(Busy::Buttons, true);
+        // busy also delays the display off
+        assert_eq!(power.tick(), None);
+        power.set_busy(Busy::Buttons, false);
+        assert_eq!(power.tick(), Some(PowerState::DisplayOff));
+        assert_eq!(power.sleep_mode(), SleepMode::Stop);
+        power.set_busy(Busy::Tone, true);
+        assert_eq!(power.sleep_mode(), SleepMode::Sleep);
+    }
+
+    #[test]
+    fn busy_until_all_the_tasks_finish() {
+        let mut power = Power::new(Policy::Stop, 1);
+        power.tick();
+        let tasks = [Busy::Buttons, Busy::Tone, Busy::Ramp, Busy::Log];
+        for &task in tasks.iter() {
+            power.set_busy(task, true);
+        }
+        for &task in tasks.iter() {
+            assert_eq!(power.sleep_mode(), SleepMode::Sleep);
+            power.set_busy(task, false);
+        }
+        assert_eq!(power.sleep_mode(), SleepMode::Stop);
+    }
+
+    #[test]
+    fn sleep_policy_never_stops() {
+        let mut power = Power::new(Policy::Sleep, 1);
+        power.tick();
+        assert_eq!(power.state(), PowerState::DisplayOff);
+        assert_eq!(power.sleep_mode(), SleepMode::Sleep);
+    }
+}
@@ -0,0 +1,196 @@
+/// Gradual wake up before an alarm: first the light and then the sound get stronger
+#[cfg(not(test))]
+use stm32f1xx_hal::{
+    gpio::{gpiob::PB0, Alternate, PushPull},
+    pac::TIM3,
+    timer::{Ch, Channel, PwmHz, Tim3NoRemap, C3},
+};
+
+/// the level of the light and of the sound at their maximum, zero is off
+pub const FULL_LEVEL: u16 = 1000;
+/// the exponential curve goes from 1 to 2^DOUBLINGS
+const DOUBLINGS: u32 = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Curve {
+    /// the same increment every second
+    Linear,
+    /// the level doubles at a constant rate, it feels more linear for the eyes and the ears
+    Exponential,
+}
+
+impl Curve {
+    /// the level for a `progress` between zero and `FULL_LEVEL`
+    pub fn level(&self, progress: u16) -> u16 {
+        let progress = u32::from(progress.min(FULL_LEVEL));
+        match self {
+            Curve::Linear => progress as u16,
+            Curve::Exponential => {
+                // ten doublings from 1 to 1024, linear between the
+                // powers of two so there is no need of floats
+                let exponent = progress * DOUBLINGS;
+                let power = 1 << (exponent / u32::from(FULL_LEVEL));
+                let fraction = exponent % u32::from(FULL_LEVEL);
+                let value = power + power * fraction / u32::from(FULL_LEVEL);
+                ((value - 1) * u32::from(FULL_LEVEL) / ((1 << DOUBLINGS) - 1)) as u16
+            }
+        }
+    }
+}
+
+/// the light and sound levels during the ramp
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Levels {
+    pub light: u16,
+    pub sound: u16,
+}
+
+/// The wake up ramp of an alarm, zero minutes is no ramp
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Ramp {
+    pub minutes: u8,
+    pub curve: Curve,
+}
+
+impl Ramp {
+    pub const OFF: Ramp = Ramp {
+        minutes: 0,
+        curve: Curve::Linear,
+    };
+    /// it has to fit in the backup registers
+    pub const MAX_MINUTES: u8 = 60;
+
+    pub fn new(minutes: u8, curve: Curve) -> Self {
+        Self {
+            minutes: minutes.min(Self::MAX_MINUTES),
+            curve,
+        }
+    }
+
+    pub fn seconds(&self) -> u32 {
+        u32::from(self.minutes) * 60
+    }
+
+    /// the epoch when the ramp of an alarm that rings at `trigger` starts
+    pub fn start(&self, trigger: u32) -> u32 {
+        trigger.saturating_sub(self.seconds())
+    }
+
+    /// the levels with the alarm ringing in `seconds_until`, `None` out of the ramp: the light
+    /// goes up in the first half and the sound in the second one
+    pub fn levels(&self, seconds_until: u32) -> Option<Levels> {
+        let length = self.seconds();
+        if length == 0 || seconds_until > length {
+            return None;
+        }
+        let progress = ((length - seconds_until) * u32::from(FULL_LEVEL) / length) as u16;
+        let half = FULL_LEVEL / 2;
+        Some(Levels {
+            light: self.curve.level(progress.min(half) * 2),
+            sound: self.curve.level(progress.saturating_sub(half) * 2),
+        })
+    }
+}
+
+/// something with a brightness between zero and `FULL_LEVEL`
+#[cfg(not(test))]
+pub trait Light {
+    fn set_brightness(&mut self, level: u16);
+}
+
+/// the wake up light in PB0 with the channel 3 of the TIM3
+#[cfg(not(test))]
+pub type PwmLight = PwmHz<TIM3, Tim3NoRemap, Ch<C3>, PB0<Alternate<PushPull>>>;
+
+#[cfg(not(test))]
+impl Light for PwmLight {
+    fn set_brightness(&mut self, level: u16) {
+        let max = u32::from(self.get_max_duty());
+        let duty = max * u32::from(level.min(FULL_LEVEL)) / u32::from(FULL_LEVEL);
+        self.set_duty(Channel::C3, duty as u16);
+        if level == 0 {
+            self.disable(Channel::C3);
+        } else {
+            self.enable(Channel::C3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn curves_go_from_zero_to_full() {
+        for curve in [Curve::Linear, Curve::Exponential].iter() {
+            assert_eq!(curve.level(0), 0);
+            assert_eq!(curve.level(FULL_LEVEL), FULL_LEVEL);
+            assert_eq!(curve.level(u16::MAX), FULL_LEVEL);
+            let mut last = 0;
+            for progress in 0..=FULL_LEVEL {
+                let level = curve.level(progress);
+                assert!(level >= last, "{:?} goes down at {}", curve, progress);
+                last = level;
+            }
+        }
+    }
+
+    #[test]
+    fn exponential_starts_slow() {
+        assert_eq!(Curve::Linear.level(500), 500);
+        assert_eq!(Curve::Exponential.level(500), 30);
+        assert_eq!(Curve::Exponential.level(900), 499);
+    }
+
+    #[test]
+    fn ramp_out_of_its_window() {
+        let ramp = Ramp::new(10, Curve::Linear);
+        assert_eq!(ramp.levels(601), None);
+        assert_eq!(Ramp::OFF.levels(0), None);
+        assert_eq!(ramp.start(10_000), 10_000 - 600);
+        assert_eq!(ramp.start(10), 0);
+    }
+
+    #[test]
+    fn light_first_and_then_sound() {
+        let ramp = Ramp::new(10, Curve::Linear);
+        let levels = |seconds| ramp.levels(seconds).unwrap();
+        assert_eq!(levels(600), Levels { light: 0, sound: 0 });
+        assert_eq!(
+            levels(450),
+            Levels {
+                light: 500,
+                sound: 0
+            }
+        );
+        assert_eq!(
+            levels(300),
+            Levels {
+                light: FULL_LEVEL,
+                sound: 0
+            }
+        );
+        assert_eq!(
+            levels(150),
+            Levels {
+                light: FULL_LEVEL,
+                sound: 500
+            }
+        );
+        assert_eq!(
+            levels(0),
+            Levels {
+                light: FULL_LEVEL,
+                sound: FULL_LEVEL
+            }
+        );
+    }
+
+    #[test]
+    fn ramp_length_is_limited() {
+        assert_eq!(
+            Ramp::new(200, Curve::Exponential).minutes,
+            Ramp::MAX_MINUTES
+        );
+    }
+}
@@ -0,0 +1,239 @@
+/// Time zones: the RTC holds UTC and the user sees the local time of a `TimeZone`
+use crate::datetime::{days_of_month, epoch_from_ymd_hms, DateTime};
+use core::convert::TryFrom;
+
+const SECONDS_PER_DAY: u32 = 86400;
+const SECONDS_PER_HOUR: i64 = 3600;
+// the 1970-01-01 was a Thursday, so the Sundays are the days `3` modulo seven
+const SUNDAY: u32 = 3;
+
+/// When the clocks go one hour forward in the summer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DstRule {
+    /// the offset never changes
+    None,
+    /// from the last Sunday of March to the last Sunday of October, at 01:00 UTC
+    Eu,
+    /// from the second Sunday of March to the first Sunday of November, at 02:00 local time
+    Us,
+}
+
+/// A fixed offset from UTC plus the daylight saving time rule
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimeZone {
+    /// seconds east of UTC of the standard time
+    offset: i32,
+    dst: DstRule,
+}
+
+impl TimeZone {
+    pub const UTC: TimeZone = TimeZone::new(0, DstRule::None);
+
+    /// `offset_minutes` of the standard (winter) time, e.g -180 for Buenos Aires
+    pub const fn new(offset_minutes: i16, dst: DstRule) -> Self {
+        Self {
+            offset: offset_minutes as i32 * 60,
+            dst,
+        }
+    }
+
+    /// true if the daylight saving time is in use at the `utc` epoch
+    pub fn is_dst(&self, utc: u32) -> bool {
+        let year = DateTime::new(utc).year;
+        let standard = i64::from(self.offset);
+        let (start, end) = match self.dst {
+            DstRule::None => return false,
+            DstRule::Eu => (
+                sunday_epoch(last_sunday(year, 3)) + SECONDS_PER_HOUR,
+                sunday_epoch(last_sunday(year, 10)) + SECONDS_PER_HOUR,
+            ),
+            // at 02:00 of the standard time it goes to 03:00, and at
+            // 02:00 of the summer time it goes back to 01:00
+            DstRule::Us => (
+                sunday_epoch(nth_sunday(year, 3, 2)) + 2 * SECONDS_PER_HOUR - standard,
+                sunday_epoch(nth_sunday(year, 11, 1)) + SECONDS_PER_HOUR - standard,
+            ),
+        };
+        (start..end).contains(&i64::from(utc))
+    }
+
+    /// seconds east of UTC at the `utc` epoch
+    pub fn offset(&self, utc: u32) -> i32 {
+        if self.is_dst(utc) {
+            self.offset + SECONDS_PER_HOUR as i32
+        } else {
+            self.offset
+        }
+    }
+
+    /// the wall clock time as seconds since 1970-01-01 00:00:00 local, it saturates at the
+    /// limits of the RTC
+    pub fn to_local(self, utc: u32) -> u32 {
+        let local = i64::from(utc) + i64::from(self.offset(utc));
+        local.clamp(0, i64::from(u32::MAX)) as u32
+    }
+
+    /// the `DateTime` that the user sees at the `utc` epoch
+    pub fn datetime(&self, utc: u32) -> DateTime {
+        DateTime::new(self.to_local(utc))
+    }
+
+    /// the UTC epoch of a wall clock time: in the hour repeated when the summer ends it is the
+    /// first one, and the hour skipped when it starts is moved one hour forward
+    pub fn to_utc(self, local: u32) -> Option<u32> {
+        let standard = i64::from(local) - i64::from(self.offset);
+        let summer = standard - SECONDS_PER_HOUR;
+        let valid = |utc: i64| u32::try_from(utc).ok();
+        if self.dst != DstRule::None {
+            if let Some(utc) = valid(summer).filter(|&utc| self.is_dst(utc)) {
+                return Some(utc);
+            }
+        }
+        valid(standard)
+    }
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        Self::UTC
+    }
+}
+
+/// the days since the epoch of the first day of `month`
+fn first_day(year: u16, month: u8) -> u32 {
+    // only after the 2106 this is out of the RTC range
+    epoch_from_ymd_hms(year, month, 1, 0, 0, 0).unwrap_or(u32::MAX) / SECONDS_PER_DAY
+}
+
+/// the days since the epoch of the last Sunday of `month`
+fn last_sunday(year: u16, month: u8) -> u32 {
+    let last = first_day(year, month) + u32::from(days_of_month(year, month)) - 1;
+    last - (last + 7 - SUNDAY) % 7
+}
+
+/// the days since the epoch of the `n`th Sunday of `month`
+fn nth_sunday(year: u16, month: u8, n: u32) -> u32 {
+    let first = first_day(year, month);
+    first + (SUNDAY + 7 - first % 7) % 7 + 7 * (n - 1)
+}
+
+fn sunday_epoch(days: u32) -> i64 {
+    i64::from(days) * i64::from(SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::datetime::fixture::epoch;
+
+    const CET: TimeZone = TimeZone::new(60, DstRule::Eu);
+    const NEW_YORK: TimeZone = TimeZone::new(-300, DstRule::Us);
+    const BUENOS_AIRES: TimeZone = TimeZone::new(-180, DstRule::None);
+
+    #[test]
+    fn sundays() {
+        assert_eq!(
+            last_sunday(2023, 3) * SECONDS_PER_DAY,
+            epoch(2023, 3, 26, 0, 0)
+        );
+        assert_eq!(
+            last_sunday(2023, 10) * SECONDS_PER_DAY,
+            epoch(2023, 10, 29, 0, 0)
+        );
+        // the last day of the month is a Sunday
+        assert_eq!(
+            last_sunday(2023, 4) * SECONDS_PER_DAY,
+            epoch(2023, 4, 30, 0, 0)
+        );
+        assert_eq!(
+            nth_sunday(2023, 3, 2) * SECONDS_PER_DAY,
+            epoch(2023, 3, 12, 0, 0)
+        );
+        assert_eq!(
+            nth_sunday(2023, 11, 1) * SECONDS_PER_DAY,
+            epoch(2023, 11, 5, 0, 0)
+        );
+        // the first day of the month is a Sunday
+        assert_eq!(
+            nth_sunday(2023, 1, 1) * SECONDS_PER_DAY,
+            epoch(2023, 1, 1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn fixed_offset() {
+        let utc = epoch(2023, 1, 1, 1, 30);
+        assert_eq!(BUENOS_AIRES.datetime(utc).day, 31);
+        assert_eq!(BUENOS_AIRES.datetime(utc).hour, 22);
+        assert_eq!(BUENOS_AIRES.to_utc(BUENOS_AIRES.to_local(utc)), Some(utc));
+        assert_eq!(TimeZone::UTC.to_local(utc), utc);
+        // the limits of the RTC
+        assert_eq!(BUENOS_AIRES.to_local(0), 0);
+        assert_eq!(BUENOS_AIRES.to_utc(u32::MAX), None);
+    }
+
+    #[test]
+    fn eu_transitions() {
+        // 2023-03-26 01:00 UTC: 02:00 CET is 03:00 CEST
+        let start = epoch(2023, 3, 26, 1, 0);
+        assert_eq!(CET.datetime(start - 1).hour, 1);
+        assert_eq!(CET.datetime(start).hour, 3);
+        // 2023-10-29 01:00 UTC: 03:00 CEST is 02:00 CET
+        let end = epoch(2023, 10, 29, 1, 0);
+        assert_eq!(CET.datetime(end - 1).hour, 2);
+        assert_eq!(CET.datetime(end - 1).min, 59);
+        assert_eq!(CET.datetime(end).hour, 2);
+        assert_eq!(CET.datetime(end).min, 0);
+        assert_eq!(CET.offset(epoch(2023, 7, 1, 0, 0)), 7200);
+        assert_eq!(CET.offset(epoch(2023, 12, 1, 0, 0)), 3600);
+    }
+
+    #[test]
+    fn us_transitions() {
+        // 2023-03-12 02:00 EST is 07:00 UTC
+        let start = epoch(2023, 3, 12, 7, 0);
+        assert!(!NEW_YORK.is_dst(start - 1));
+        assert!(NEW_YORK.is_dst(start));
+        assert_eq!(NEW_YORK.datetime(start).hour, 3);
+        // 2023-11-05 02:00 EDT is 06:00 UTC
+        let end = epoch(2023, 11, 5, 6, 0);
+        assert!(NEW_YORK.is_dst(end - 1));
+        assert!(!NEW_YORK.is_dst(end));
+        assert_eq!(NEW_YORK.datetime(end).hour, 1);
+    }
+
+    #[test]
+    fn local_to_utc_around_the_transitions() {
+        // the skipped hour goes one hour forward
+        let skipped = epoch(2023, 3, 26, 2, 30);
+        assert_eq!(CET.to_utc(skipped), Some(epoch(2023, 3, 26, 1, 30)));
+        assert_eq!(CET.datetime(epoch(2023, 3, 26, 1, 30)).hour, 3);
+        // the repeated hour is the first one, in the summer time
+        let repeated = epoch(2023, 10, 29, 2, 30);
+        assert_eq!(CET.to_utc(repeated), Some(epoch(2023, 10, 29, 0, 30)));
+        let repeated = epoch(2023, 11, 5, 1, 30);
+        assert_eq!(NEW_YORK.to_utc(repeated), Some(epoch(2023, 11, 5, 5, 30)));
+    }
+
+    #[test]
+    fn local_time_round_trip_every_minute_of_a_year() {
+        for zone in [CET, NEW_YORK, BUENOS_AIRES].iter() {
+            let mut utc = epoch(2023, 1, 1, 0, 0);
+            let mut last = zone.to_local(utc);
+            while utc < epoch(2024, 1, 1, 0, 0) {
+                utc += 60;
+                let local = zone.to_local(utc);
+                let back = zone.to_utc(local).unwrap();
+                // only the second pass of the repeated hour is not the same
+                if back != utc {
+                    assert_eq!(back + 3600, utc);
+                    assert!(zone.is_dst(back) && !zone.is_dst(utc));
+                }
+                // the local time goes forward except when the summer ends
+                let step = i64::from(local) - i64::from(last);
+                assert!(step == 60 || step == 60 + 3600 || step == 60 - 3600);
+                last = local;
+            }
+        }
+    }
+}
@@ -0,0 +1,317 @@
+/// Tones in a buzzer, the patterns are sequenced here and the PWM is behind the `Buzzer` trait
+use crate::ramp::FULL_LEVEL;
+#[cfg(not(test))]
+use stm32f1xx_hal::{
+    gpio::{gpioa::PA0, Alternate, PushPull},
+    pac::TIM2,
+    prelude::*,
+    timer::{Ch, Channel, PwmHz, Tim2NoRemap, C1},
+};
+
+//-------------------------------------------------------------------------
+//                        notes frequencies (Hz)
+//-------------------------------------------------------------------------
+pub const C5: u16 = 523;
+pub const D5: u16 = 587;
+pub const E5: u16 = 659;
+pub const F5: u16 = 698;
+pub const G5: u16 = 784;
+pub const A5: u16 = 880;
+pub const B5: u16 = 988;
+pub const C6: u16 = 1047;
+/// the resonance of the common piezo buzzers, the loudest beep
+pub const BEEP: u16 = 2730;
+
+/// a frequency during some milliseconds, a zero frequency is a silence
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Note {
+    pub freq: u16,
+    pub duration: u16,
+}
+
+impl Note {
+    pub const fn new(freq: u16, duration: u16) -> Self {
+        Self { freq, duration }
+    }
+
+    pub const fn rest(duration: u16) -> Self {
+        Self { freq: 0, duration }
+    }
+
+    pub fn is_rest(&self) -> bool {
+        self.freq == 0
+    }
+}
+
+const BEEP_BEEP: [Note; 4] = [
+    Note::new(BEEP, 100),
+    Note::rest(100),
+    Note::new(BEEP, 100),
+    Note::rest(700),
+];
+
+const RISING: [Note; 9] = [
+    Note::new(C5, 120),
+    Note::new(D5, 120),
+    Note::new(E5, 120),
+    Note::new(F5, 120),
+    Note::new(G5, 120),
+    Note::new(A5, 120),
+    Note::new(B5, 120),
+    Note::new(C6, 240),
+    Note::rest(500),
+];
+
+// the first notes of "Oh when the saints"
+const SAINTS: [Note; 9] = [
+    Note::new(C5, 200),
+    Note::new(E5, 200),
+    Note::new(F5, 200),
+    Note::new(G5, 800),
+    Note::rest(200),
+    Note::new(C5, 200),
+    Note::new(E5, 200),
+    Note::new(F5, 200),
+    Note::new(G5, 800),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    BeepBeep,
+    Rising,
+    Melody,
+}
+
+impl Pattern {
+    pub fn notes(&self) -> &'static [Note] {
+        match self {
+            Pattern::BeepBeep => &BEEP_BEEP,
+            Pattern::Rising => &RISING,
+            Pattern::Melody => &SAINTS,
+        }
+    }
+}
+
+/// gives the notes of a pattern one by one, the caller waits the duration of each one
+#[derive(Debug, Clone)]
+pub struct Sequencer {
+    notes: &'static [Note],
+    index: usize,
+    looping: bool,
+    /// between zero and `FULL_LEVEL`
+    volume: u16,
+}
+
+impl Sequencer {
+    pub const fn new() -> Self {
+        Self {
+            notes: &[],
+            index: 0,
+            looping: false,
+            volume: FULL_LEVEL,
+        }
+    }
+
+    /// restart with `pattern`, a looping pattern plays until `stop`
+    pub fn start(&mut self, pattern: Pattern, looping: bool) {
+        self.notes = pattern.notes();
+        self.index = 0;
+        self.looping = looping;
+    }
+
+    pub fn stop(&mut self) {
+        self.notes = &[];
+        self.index = 0;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.index < self.notes.len()
+    }
+
+    /// the volume of the next notes, it is kept between patterns
+    pub fn set_volume(&mut self, volume: u16) {
+        self.volume = volume.min(FULL_LEVEL);
+    }
+
+    pub fn volume(&self) -> u16 {
+        self.volume
+    }
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for Sequencer {
+    type Item = Note;
+
+    fn next(&mut self) -> Option<Note> {
+        let note = *self.notes.get(self.index)?;
+        self.index += 1;
+        if self.looping && self.index == self.notes.len() {
+            self.index = 0;
+        }
+        Some(note)
+    }
+}
+
+/// something that makes a sound with a frequency
+pub trait Buzzer {
+    fn tone(&mut self, freq: u16);
+    fn mute(&mut self);
+
+    fn play(&mut self, note: Note) {
+        if note.is_rest() {
+            self.mute()
+        } else {
+            self.tone(note.freq)
+        }
+    }
+}
+
+/// the buzzer in PA0 with the channel 1 of the TIM2
+#[cfg(not(test))]
+pub struct PwmBuzzer {
+    pwm: PwmHz<TIM2, Tim2NoRemap, Ch<C1>, PA0<Alternate<PushPull>>>,
+    volume: u16,
+}
+
+#[cfg(not(test))]
+impl PwmBuzzer {
+    pub fn new(pwm: PwmHz<TIM2, Tim2NoRemap, Ch<C1>, PA0<Alternate<PushPull>>>) -> Self {
+        Self {
+            pwm,
+            volume: FULL_LEVEL,
+        }
+    }
+
+    /// between zero and `FULL_LEVEL`, for the next tones
+    pub fn set_volume(&mut self, volume: u16) {
+        self.volume = volume.min(FULL_LEVEL);
+    }
+}
+
+#[cfg(not(test))]
+impl Buzzer for PwmBuzzer {
+    fn tone(&mut self, freq: u16) {
+        self.pwm.set_period(u32::from(freq).Hz());
+        // zero is a max duty of 2^16, and the loudest is the half
+        let half = match self.pwm.get_max_duty() {
+            0 => 1 << 15,
+            max => u32::from(max / 2),
+        };
+        let duty = half * u32::from(self.volume) / u32::from(FULL_LEVEL);
+        self.pwm.set_duty(Channel::C1, duty as u16);
+        self.pwm.enable(Channel::C1);
+    }
+
+    fn mute(&mut self) {
+        self.pwm.disable(Channel::C1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::vec::Vec;
+
+    /// records what the sequencer sends to the buzzer
+    #[derive(Default)]
+    struct FakeBuzzer {
+        steps: Vec<(u16, u16)>,
+    }
+
+    impl Buzzer for FakeBuzzer {
+        fn tone(&mut self, freq: u16) {
+            self.steps.push((freq, 0));
+        }
+
+        fn mute(&mut self) {
+            self.steps.push((0, 0));
+        }
+    }
+
+    /// plays the sequencer like the tone task, but without waiting
+    fn record(sequencer: &mut Sequencer, max_steps: usize) -> Vec<(u16, u16)> {
+        let mut buzzer = FakeBuzzer::default();
+        for note in sequencer.take(max_steps) {
+            buzzer.play(note);
+            buzzer.steps.last_mut().unwrap().1 = note.duration;
+        }
+        buzzer.steps
+    }
+
+    #[test]
+    fn idle_sequencer_plays_nothing() {
+        let mut sequencer = Sequencer::new();
+        assert!(!sequencer.is_playing());
+        assert_eq!(sequencer.next(), None);
+    }
+
+    #[test]
+    fn beep_beep_once() {
+        let mut sequencer = Sequencer::new();
+        sequencer.start(Pattern::BeepBeep, false);
+        assert!(sequencer.is_playing());
+        assert_eq!(
+            record(&mut sequencer, 100),
+            [(BEEP, 100), (0, 100), (BEEP, 100), (0, 700)]
+        );
+        assert!(!sequencer.is_playing());
+    }
+
+    #[test]
+    fn rising_goes_up() {
+        let mut sequencer = Sequencer::new();
+        sequencer.start(Pattern::Rising, false);
+        let steps = record(&mut sequencer, 100);
+        let tones: Vec<u16> = steps
+            .iter()
+            .map(|&(freq, _)| freq)
+            .filter(|&freq| freq != 0)
+            .collect();
+        assert_eq!(tones.len(), 8);
+        assert!(tones.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(steps.last(), Some(&(0, 500)));
+    }
+
+    #[test]
+    fn looping_pattern_repeats_until_stop() {
+        let mut sequencer = Sequencer::new();
+        sequencer.start(Pattern::BeepBeep, true);
+        let steps = record(&mut sequencer, 10);
+        assert_eq!(steps.len(), 10);
+        assert_eq!(steps[..4], steps[4..8]);
+        assert_eq!(steps[8..], steps[..2]);
+        assert!(sequencer.is_playing());
+        sequencer.stop();
+        assert!(!sequencer.is_playing());
+        assert_eq!(sequencer.next(), None);
+    }
+
+    #[test]
+    fn start_restarts_from_the_first_note() {
+        let mut sequencer = Sequencer::new();
+        sequencer.start(Pattern::Melody, false);
+        sequencer.next();
+        sequencer.next();
+        sequencer.start(Pattern::Melody, false);
+        let total: u32 = sequencer.map(|note| u32::from(note.duration)).sum();
+        assert_eq!(total, 3000);
+    }
+
+    #[test]
+    fn volume_is_kept_between_patterns() {
+        let mut sequencer = Sequencer::new();
+        assert_eq!(sequencer.volume(), FULL_LEVEL);
+        sequencer.set_volume(2 * FULL_LEVEL);
+        assert_eq!(sequencer.volume(), FULL_LEVEL);
+        sequencer.set_volume(100);
+        sequencer.start(Pattern::Rising, true);
+        sequencer.stop();
+        sequencer.start(Pattern::BeepBeep, false);
+        assert_eq!(sequencer.volume(), 100);
+    }
+}
@@ -1,7 +1,11 @@
 /// User interface primitives
+use crate::alarm::{snooze_until, Snooze, MAX_ALARMS};
+use crate::datetime::{days_of_month, epoch_from_ymd_hms, DateTime};
+use crate::locale::{Language, Locale, ENGLISH};
+use crate::timezone::TimeZone;
 use embedded_graphics::{
     image::{Image, ImageRawLE},
-    mono_font::{ascii::FONT_9X15, MonoTextStyleBuilder},
+    mono_font::{iso_8859_1::FONT_9X15, MonoTextStyleBuilder},
     pixelcolor::BinaryColor,
     prelude::*,
     text::Text,
@@ -15,7 +19,12 @@ use embedded_graphics::{
 // const CHAR_WIDTH: i32 = 6;
 
 /// This is the principal function that renders all the menu states
-pub fn draw_menu<D>(target: &mut D, state: ClockState, msg: Option<&str>) -> Result<(), D::Error>
+pub fn draw_menu<D>(
+    target: &mut D,
+    state: ClockState,
+    msg: Option<&str>,
+    locale: &Locale,
+) -> Result<(), D::Error>
 where
     D: DrawTarget<Color = BinaryColor>,
 {
@@ -32,12 +41,20 @@ where
     //     .build();
 
     match (state, msg) {
-        (ClockState::Time, Some(time)) => {
+        (ClockState::Time | ClockState::SetTime | ClockState::AlarmList, Some(time)) => {
             Text::new(time, Point::new(0, 13), normal).draw(target)?;
         }
-        (ClockState::Time, None) => {}
-        (ClockState::Alarm, _) => {
-            Text::new("--- Alarm ---", Point::new(0, 13), normal).draw(target)?;
+        (ClockState::Time | ClockState::SetTime | ClockState::AlarmList, None) => {}
+        (ClockState::Alarm | ClockState::Ringing | ClockState::Settings, alarm) => {
+            let title = match state {
+                ClockState::Ringing => locale.ringing_title,
+                ClockState::Settings => locale.settings_title,
+                _ => locale.alarm_title,
+            };
+            Text::new(title, Point::new(0, 13), normal).draw(target)?;
+            if let Some(alarm) = alarm {
+                Text::new(alarm, Point::new(0, 35), normal).draw(target)?;
+            }
         }
         (ClockState::Image, _) => {
             Image::new(&logo_image, Point::new(32, 0)).draw(target)?;
@@ -47,23 +64,182 @@ where
     Ok(())
 }
 
+//-------------------------------------------------------------------------
+//                        time setting
+//-------------------------------------------------------------------------
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimeField {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Min,
+}
+
+impl TimeField {
+    pub fn name(self, locale: &Locale) -> &'static str {
+        locale.fields[self as usize]
+    }
+}
+
+impl ::core::fmt::Display for TimeField {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
+        f.write_str(self.name(&ENGLISH))
+    }
+}
+
+/// The date and time that the user is editing with the buttons, field by field
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimeEditor {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub min: u8,
+    pub field: TimeField,
+}
+
+impl TimeEditor {
+    pub const MIN_YEAR: u16 = 2000;
+    pub const MAX_YEAR: u16 = 2099;
+
+    /// start editing from the time `now`, in the year field
+    pub fn new(now: u32) -> Self {
+        let now = DateTime::new(now);
+        let mut editor = Self {
+            year: now.year.clamp(Self::MIN_YEAR, Self::MAX_YEAR),
+            month: now.month,
+            day: now.day,
+            hour: now.hour,
+            min: now.min,
+            field: TimeField::Year,
+        };
+        editor.clamp_day();
+        editor
+    }
+
+    pub fn increment(&mut self) {
+        self.step(true);
+    }
+
+    pub fn decrement(&mut self) {
+        self.step(false);
+    }
+
+    /// confirm the actual field, returns the epoch to set when the last field is confirmed
+    pub fn confirm(&mut self) -> Option<u32> {
+        use TimeField::*;
+        self.field = match self.field {
+            Year => Month,
+            Month => Day,
+            Day => Hour,
+            Hour => Min,
+            Min => return self.to_epoch(),
+        };
+        None
+    }
+
+    pub fn to_epoch(self) -> Option<u32> {
+        epoch_from_ymd_hms(self.year, self.month, self.day, self.hour, self.min, 0).ok()
+    }
+
+    fn step(&mut self, up: bool) {
+        use TimeField::*;
+        match self.field {
+            Year => {
+                self.year = if up && self.year >= Self::MAX_YEAR {
+                    Self::MIN_YEAR
+                } else if !up && self.year <= Self::MIN_YEAR {
+                    Self::MAX_YEAR
+                } else if up {
+                    self.year + 1
+                } else {
+                    self.year - 1
+                };
+                self.clamp_day();
+            }
+            Month => {
+                self.month = wrap(self.month, 1, 12, up);
+                self.clamp_day();
+            }
+            Day => self.day = wrap(self.day, 1, days_of_month(self.year, self.month), up),
+            Hour => self.hour = wrap(self.hour, 0, 23, up),
+            Min => self.min = wrap(self.min, 0, 59, up),
+        }
+    }
+
+    fn clamp_day(&mut self) {
+        self.day = self.day.clamp(1, days_of_month(self.year, self.month));
+    }
+
+    /// the date, the time and the field that is being edited in the language of `locale`
+    pub fn write<W: ::core::fmt::Write>(&self, w: &mut W, locale: &Locale) -> ::core::fmt::Result {
+        write!(
+            w,
+            "{:04}-{:02}-{:02}\n{:02}:{:02}\n{} {}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.min,
+            locale.set,
+            self.field.name(locale)
+        )
+    }
+}
+
+/// Shows the date, the time and the field that is being edited
+impl ::core::fmt::Display for TimeEditor {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> Result<(), ::core::fmt::Error> {
+        self.write(f, &ENGLISH)
+    }
+}
+
+/// increment or decrement `value` inside `min..=max` with wrap around
+fn wrap(value: u8, min: u8, max: u8, up: bool) -> u8 {
+    match (up, value) {
+        (true, v) if v >= max => min,
+        (true, v) => v + 1,
+        (false, v) if v <= min => max,
+        (false, v) => v - 1,
+    }
+}
+
 //-------------------------------------------------------------------------
 //                        finite state machine for the menu
 //-------------------------------------------------------------------------
 #[derive(Copy, Clone)]
 pub enum Msg {
-    Up,       // Up button
-    Down,     // Down button
-    Continue, // Continue in the actual state
+    Up,          // Up button
+    Down,        // Down button
+    Enter,       // Enter button
+    Back,        // Enter button long press
+    Continue,    // Continue in the actual state (every RTC second)
+    Ring(usize), // The alarm with this index starts to ring
 }
 
-type BackgroundFlag = bool;
+/// Side effects of a state transition that the caller must apply to the hardware
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// write this epoch to the RTC
+    SetTime(u32),
+    /// enable or disable the alarm with this index
+    ToggleAlarm(usize),
+    /// stop the tone and ring again the alarm with this index at the epoch
+    Snooze(usize, u32),
+    /// stop the tone of the alarm with this index
+    Dismiss(usize),
+}
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ClockState {
     Time,
     Alarm,
     Image,
+    SetTime,
+    AlarmList,
+    Ringing,
+    Settings,
 }
 
 // TODO(elsuizo: 2023-04-10): what is this???
@@ -73,29 +249,445 @@ impl ClockState {
     // }
 }
 
+/// The alarm that is ringing (or was the last one)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RingingAlarm {
+    pub index: usize,
+    /// epoch when it started to ring
+    pub since: u32,
+    /// times that it was snoozed since the last dismiss
+    pub snoozes: u8,
+}
+
 #[derive(Copy, Clone)]
 pub struct ClockFSM {
     pub state: ClockState,
+    pub editor: TimeEditor,
+    /// index of the alarm selected in the alarm list
+    pub selected: usize,
+    pub snooze: Snooze,
+    pub ringing: RingingAlarm,
+    /// the user sees and edits the local time, the RTC is in UTC
+    pub zone: TimeZone,
+    /// of the menu and the date, it is changed in the settings
+    pub language: Language,
 }
 
 impl ClockFSM {
     pub fn init(state: ClockState) -> Self {
-        Self { state }
+        Self {
+            state,
+            editor: TimeEditor::new(0),
+            selected: 0,
+            snooze: Snooze::default(),
+            ringing: RingingAlarm {
+                index: 0,
+                since: 0,
+                snoozes: 0,
+            },
+            zone: TimeZone::UTC,
+            language: Language::default(),
+        }
+    }
+
+    pub fn locale(&self) -> &'static Locale {
+        self.language.locale()
+    }
+
+    pub fn with_zone(self, zone: TimeZone) -> Self {
+        Self { zone, ..self }
+    }
+
+    pub fn with_snooze(self, snooze: Snooze) -> Self {
+        Self { snooze, ..self }
     }
 
-    pub fn next_state(&mut self, msg: Msg) {
+    /// `now` is the actual RTC epoch (UTC), used as the starting point when the time is edited
+    pub fn next_state(&mut self, msg: Msg, now: u32) -> Option<Action> {
         use ClockState::*;
         use Msg::*;
 
+        let mut action = None;
+        let ringing = self.ringing;
         self.state = match (self.state, msg) {
+            // an alarm interrupts whatever the user is doing
+            (_, Ring(index)) => {
+                // the count of snoozes continues only if it is the same alarm
+                let snoozes = if index == ringing.index {
+                    ringing.snoozes
+                } else {
+                    0
+                };
+                self.ringing = RingingAlarm {
+                    index,
+                    since: now,
+                    snoozes,
+                };
+                Ringing
+            }
+            (Ringing, Up | Down) if ringing.snoozes < self.snooze.max_count => {
+                match snooze_until(now, self.snooze.minutes) {
+                    Some(until) => {
+                        self.ringing.snoozes += 1;
+                        action = Some(Action::Snooze(ringing.index, until));
+                        Time
+                    }
+                    None => Ringing,
+                }
+            }
+            (Ringing, Up | Down) => Ringing,
+            (Ringing, Enter | Back) => {
+                self.ringing.snoozes = 0;
+                action = Some(Action::Dismiss(ringing.index));
+                Time
+            }
+            (Ringing, Continue) => {
+                let timeout = self.snooze.ring_timeout;
+                if timeout != 0 && now.saturating_sub(ringing.since) >= u32::from(timeout) {
+                    self.ringing.snoozes = 0;
+                    action = Some(Action::Dismiss(ringing.index));
+                    Time
+                } else {
+                    Ringing
+                }
+            }
+            (SetTime, Up) => {
+                self.editor.increment();
+                SetTime
+            }
+            (SetTime, Down) => {
+                self.editor.decrement();
+                SetTime
+            }
+            // the editor has the local time
+            (SetTime, Enter) => match self
+                .editor
+                .confirm()
+                .and_then(|local| self.zone.to_utc(local))
+            {
+                Some(epoch) => {
+                    action = Some(Action::SetTime(epoch));
+                    Time
+                }
+                None => SetTime,
+            },
+            (SetTime, Back) => Time,
+            (SetTime, Continue) => SetTime,
+            (AlarmList, Up) => {
+                self.selected = (self.selected + 1) % MAX_ALARMS;
+                AlarmList
+            }
+            (AlarmList, Down) => {
+                self.selected = (self.selected + MAX_ALARMS - 1) % MAX_ALARMS;
+                AlarmList
+            }
+            (AlarmList, Enter) => {
+                action = Some(Action::ToggleAlarm(self.selected));
+                AlarmList
+            }
+            (AlarmList, Back) => Alarm,
+            (AlarmList, Continue) => AlarmList,
+            (Time, Enter) => {
+                self.editor = TimeEditor::new(self.zone.to_local(now));
+                SetTime
+            }
+            (Alarm, Enter) => {
+                self.selected = 0;
+                AlarmList
+            }
+            (Time | Alarm, Back) => Time,
             (Time, Up) => Alarm,
             (Time, Continue) => Time,
             (Alarm, Down) => Time,
-            (Alarm, Up) => Image,
+            (Alarm, Up) => Settings,
+            (Settings, Enter) => {
+                self.language = self.language.next();
+                Settings
+            }
+            (Settings, Up) => Image,
+            (Settings, Down) => Alarm,
+            (Settings, Back) => Time,
+            (Settings, Continue) => Settings,
             (Alarm, Continue) => Alarm,
             (Time, Down) => Image,
+            (Image, Continue) => Image,
             (Image, _) => Time,
-            // (Image, Continue) => Image,
+        };
+        action
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::datetime::fixture::{epoch, NOW};
+    use core::fmt::Write;
+
+    fn set(editor: &mut TimeEditor, field: TimeField, steps: i32) {
+        editor.field = field;
+        for _ in 0..steps.abs() {
+            if steps > 0 {
+                editor.increment()
+            } else {
+                editor.decrement()
+            }
+        }
+    }
+
+    #[test]
+    fn every_screen_is_drawn() {
+        use embedded_graphics::mock_display::MockDisplay;
+        use ClockState::*;
+        for language in [Language::English, Language::Spanish].iter() {
+            for &state in [Time, Alarm, Image, SetTime, AlarmList, Ringing, Settings].iter() {
+                let mut display = MockDisplay::new();
+                display.set_allow_out_of_bounds_drawing(true);
+                display.set_allow_overdraw(true);
+                draw_menu(&mut display, state, Some("07:00"), language.locale()).unwrap();
+                assert_ne!(display, MockDisplay::new(), "{:?}", state);
+            }
+        }
+    }
+
+    #[test]
+    fn editor_wraps_around() {
+        let mut editor = TimeEditor::new(NOW);
+        set(&mut editor, TimeField::Min, 42);
+        assert_eq!(editor.min, 0);
+        set(&mut editor, TimeField::Min, -1);
+        assert_eq!(editor.min, 59);
+        set(&mut editor, TimeField::Hour, 2);
+        assert_eq!(editor.hour, 0);
+        set(&mut editor, TimeField::Month, 9);
+        assert_eq!(editor.month, 1);
+        set(&mut editor, TimeField::Year, -(2023 - 2000 + 1));
+        assert_eq!(editor.year, TimeEditor::MAX_YEAR);
+        set(&mut editor, TimeField::Year, 1);
+        assert_eq!(editor.year, TimeEditor::MIN_YEAR);
+    }
+
+    #[test]
+    fn editor_clamps_day_of_month() {
+        let mut editor = TimeEditor::new(NOW);
+        set(&mut editor, TimeField::Day, 20);
+        assert_eq!(editor.day, 1);
+        set(&mut editor, TimeField::Day, -1);
+        assert_eq!(editor.day, 30);
+        set(&mut editor, TimeField::Month, -2);
+        assert_eq!((editor.month, editor.day), (2, 28));
+        set(&mut editor, TimeField::Year, 1);
+        set(&mut editor, TimeField::Day, 1);
+        assert_eq!((editor.year, editor.day), (2024, 29));
+        set(&mut editor, TimeField::Year, -1);
+        assert_eq!(editor.day, 28);
+    }
+
+    #[test]
+    fn editor_starts_in_range() {
+        let editor = TimeEditor::new(0);
+        assert_eq!((editor.year, editor.month, editor.day), (2000, 1, 1));
+    }
+
+    #[test]
+    fn fsm_sets_the_time() {
+        let mut fsm = ClockFSM::init(ClockState::Time);
+        assert_eq!(fsm.next_state(Msg::Enter, NOW), None);
+        assert_eq!(fsm.state, ClockState::SetTime);
+        // year, month, day and hour unchanged
+        for _ in 0..4 {
+            assert_eq!(fsm.next_state(Msg::Enter, 0), None);
+        }
+        fsm.next_state(Msg::Up, 0);
+        fsm.next_state(Msg::Up, 0);
+        assert_eq!(
+            fsm.next_state(Msg::Enter, 0),
+            Some(Action::SetTime(NOW + 2 * 60))
+        );
+        assert_eq!(fsm.state, ClockState::Time);
+    }
+
+    #[test]
+    fn fsm_keeps_the_state_on_continue() {
+        let mut fsm = ClockFSM::init(ClockState::Time);
+        for state in [ClockState::Image, ClockState::Alarm, ClockState::Time].iter() {
+            fsm.state = *state;
+            fsm.next_state(Msg::Continue, NOW);
+            assert_eq!(fsm.state, *state);
+        }
+        fsm.state = ClockState::Image;
+        fsm.next_state(Msg::Up, NOW);
+        assert_eq!(fsm.state, ClockState::Time);
+    }
+
+    #[test]
+    fn fsm_back_cancels_time_setting() {
+        let mut fsm = ClockFSM::init(ClockState::Time);
+        fsm.next_state(Msg::Enter, NOW);
+        fsm.next_state(Msg::Up, NOW);
+        assert_eq!(fsm.next_state(Msg::Back, NOW), None);
+        assert_eq!(fsm.state, ClockState::Time);
+    }
+
+    #[test]
+    fn fsm_enters_and_exits_the_alarm_list() {
+        let mut fsm = ClockFSM::init(ClockState::Time);
+        fsm.next_state(Msg::Up, NOW);
+        assert_eq!(fsm.state, ClockState::Alarm);
+        fsm.next_state(Msg::Enter, NOW);
+        assert_eq!(fsm.state, ClockState::AlarmList);
+        fsm.next_state(Msg::Down, NOW);
+        assert_eq!(fsm.selected, MAX_ALARMS - 1);
+        fsm.next_state(Msg::Up, NOW);
+        fsm.next_state(Msg::Up, NOW);
+        assert_eq!(
+            fsm.next_state(Msg::Enter, NOW),
+            Some(Action::ToggleAlarm(1))
+        );
+        fsm.next_state(Msg::Back, NOW);
+        assert_eq!(fsm.state, ClockState::Alarm);
+        fsm.next_state(Msg::Back, NOW);
+        assert_eq!(fsm.state, ClockState::Time);
+    }
+
+    #[test]
+    fn fsm_changes_the_language_in_the_settings() {
+        let mut fsm = ClockFSM::init(ClockState::Time);
+        fsm.next_state(Msg::Up, NOW);
+        fsm.next_state(Msg::Up, NOW);
+        assert_eq!(fsm.state, ClockState::Settings);
+        assert_eq!(fsm.next_state(Msg::Enter, NOW), None);
+        assert_eq!(fsm.language, Language::Spanish);
+        fsm.next_state(Msg::Continue, NOW);
+        assert_eq!(fsm.state, ClockState::Settings);
+        fsm.next_state(Msg::Up, NOW);
+        assert_eq!(fsm.state, ClockState::Image);
+        fsm.state = ClockState::Settings;
+        fsm.next_state(Msg::Back, NOW);
+        assert_eq!(fsm.state, ClockState::Time);
+        assert_eq!(fsm.locale().name, "Español");
+    }
+
+    #[test]
+    fn editor_in_spanish() {
+        let mut editor = TimeEditor::new(NOW);
+        editor.field = TimeField::Day;
+        let mut text: heapless::String<64> = heapless::String::new();
+        editor.write(&mut text, Language::Spanish.locale()).unwrap();
+        assert_eq!(text, "2023-04-11\n22:18\nfijar día");
+        text.clear();
+        write!(&mut text, "{}", editor).unwrap();
+        assert_eq!(text, "2023-04-11\n22:18\nset day");
+    }
+
+    /// the FSM after the alarm `index` started to ring at `now`
+    fn ringing(index: usize, now: u32) -> ClockFSM {
+        let mut fsm = ClockFSM::init(ClockState::Time).with_snooze(Snooze {
+            minutes: 10,
+            max_count: 2,
+            ring_timeout: 60,
+        });
+        fsm.next_state(Msg::Ring(index), now);
+        assert_eq!(fsm.state, ClockState::Ringing);
+        fsm
+    }
+
+    #[test]
+    fn fsm_ring_interrupts_the_time_setting() {
+        let mut fsm = ClockFSM::init(ClockState::Time);
+        fsm.next_state(Msg::Enter, NOW);
+        assert_eq!(fsm.next_state(Msg::Ring(2), NOW), None);
+        assert_eq!(fsm.state, ClockState::Ringing);
+        assert_eq!(fsm.ringing.index, 2);
+    }
+
+    #[test]
+    fn fsm_snooze_across_midnight() {
+        let now = epoch(2023, 4, 11, 23, 55);
+        let mut fsm = ringing(1, now);
+        assert_eq!(
+            fsm.next_state(Msg::Up, now + 20),
+            Some(Action::Snooze(1, epoch(2023, 4, 12, 0, 5) + 20))
+        );
+        assert_eq!(fsm.state, ClockState::Time);
+        assert_eq!(fsm.ringing.snoozes, 1);
+    }
+
+    #[test]
+    fn fsm_snooze_across_month_and_year() {
+        let now = epoch(2023, 4, 30, 23, 59);
+        let mut fsm = ringing(0, now);
+        assert_eq!(
+            fsm.next_state(Msg::Down, now),
+            Some(Action::Snooze(0, epoch(2023, 5, 1, 0, 9)))
+        );
+        let now = epoch(2023, 12, 31, 23, 50);
+        let mut fsm = ringing(0, now);
+        assert_eq!(
+            fsm.next_state(Msg::Up, now),
+            Some(Action::Snooze(0, epoch(2024, 1, 1, 0, 0)))
+        );
+    }
+
+    #[test]
+    fn fsm_snooze_limit_then_dismiss() {
+        let mut fsm = ringing(0, NOW);
+        for count in 1..=2 {
+            assert!(matches!(
+                fsm.next_state(Msg::Up, NOW),
+                Some(Action::Snooze(0, _))
+            ));
+            assert_eq!(fsm.ringing.snoozes, count);
+            fsm.next_state(Msg::Ring(0), NOW);
+        }
+        // no more snoozes, only the dismiss stops it
+        assert_eq!(fsm.next_state(Msg::Up, NOW), None);
+        assert_eq!(fsm.state, ClockState::Ringing);
+        assert_eq!(fsm.next_state(Msg::Enter, NOW), Some(Action::Dismiss(0)));
+        assert_eq!(fsm.state, ClockState::Time);
+        // and the count starts again
+        fsm.next_state(Msg::Ring(0), NOW);
+        assert!(fsm.next_state(Msg::Down, NOW).is_some());
+    }
+
+    #[test]
+    fn fsm_other_alarm_restarts_the_snooze_count() {
+        let mut fsm = ringing(0, NOW);
+        fsm.next_state(Msg::Up, NOW);
+        fsm.next_state(Msg::Ring(3), NOW);
+        assert_eq!(fsm.ringing.snoozes, 0);
+    }
+
+    #[test]
+    fn fsm_ring_timeout_stops_the_alarm() {
+        let mut fsm = ringing(1, NOW);
+        assert_eq!(fsm.next_state(Msg::Continue, NOW + 59), None);
+        assert_eq!(fsm.state, ClockState::Ringing);
+        assert_eq!(
+            fsm.next_state(Msg::Continue, NOW + 60),
+            Some(Action::Dismiss(1))
+        );
+        assert_eq!(fsm.state, ClockState::Time);
+        // zero is never
+        let mut fsm = ClockFSM::init(ClockState::Time).with_snooze(Snooze {
+            ring_timeout: 0,
+            ..Snooze::default()
+        });
+        fsm.next_state(Msg::Ring(0), NOW);
+        assert_eq!(fsm.next_state(Msg::Continue, NOW + 100_000), None);
+    }
+
+    #[test]
+    fn fsm_edits_the_local_time() {
+        use crate::timezone::DstRule;
+
+        let mut fsm =
+            ClockFSM::init(ClockState::Time).with_zone(TimeZone::new(-180, DstRule::None));
+        fsm.next_state(Msg::Enter, NOW);
+        // 22:18 UTC is 19:18 in Buenos Aires
+        assert_eq!((fsm.editor.hour, fsm.editor.min), (19, 18));
+        for _ in 0..4 {
+            fsm.next_state(Msg::Enter, NOW);
         }
+        assert_eq!(fsm.next_state(Msg::Enter, NOW), Some(Action::SetTime(NOW)));
     }
 }